@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rocket_cors;
+
+use rocket_cors::template::PathTemplate;
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = str::from_utf8(data) {
+        if let Ok(template) = PathTemplate::parse(input) {
+            // Printing a parsed template must give back the same template.
+            let printed = template.to_string();
+            assert_eq!(PathTemplate::parse(&printed), Ok(template));
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rocket_cors;
+
+use rocket_cors::matching;
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = str::from_utf8(data) {
+        // The first line is the template, the rest is the request path.
+        let mut parts = input.splitn(2, '\n');
+        let template = parts.next().unwrap_or("");
+        let path = parts.next().unwrap_or("");
+        let _ = matching::path_matches(template, path);
+        let _ = matching::path_matches(template, &matching::literalize(template));
+    }
+});
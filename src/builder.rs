@@ -0,0 +1,335 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A fluent API to declare endpoints:
+//!
+//! ```
+//! # extern crate rocket;
+//! # extern crate rocket_cors;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//!
+//! let cors = CORS::build()
+//!     .allow("/api/users/:id").methods(&[Method::Get, Method::Put])
+//!     .origins(&["https://app.example.com"])
+//!     .and()
+//!     .allow("/health")
+//!     .any_origin()
+//!     .finish()
+//!     .expect("valid endpoints");
+//! # }
+//! ```
+//!
+//! Endpoints sharing a base path can be declared as a group:
+//!
+//! ```
+//! # extern crate rocket;
+//! # extern crate rocket_cors;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::builder::EndpointGroup;
+//!
+//! let api = EndpointGroup::base("/api/v1")
+//!     .methods(&[Method::Get])
+//!     .path("/users")
+//!     .path_with("/users/:id", &[Method::Put, Method::Delete]);
+//! let cors = CORS::build().group(api).finish().expect("valid endpoints");
+//! # }
+//! ```
+
+use {AllowedOrigins, CORSEndpoint, CORS};
+use policy::EndpointPolicy;
+use rocket::http::Method;
+use template::{join_paths, IntoEndpointPath, TemplateError};
+
+/// Collects endpoints, created by `CORS::build()`.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    endpoints: Vec<CORSEndpoint>,
+    /// The origins allowed on the endpoints with each index, instead of
+    /// those of the fairing.
+    origins: Vec<(usize, AllowedOrigins)>,
+}
+
+impl Builder {
+    /// Starts declaring an endpoint for `path`, a template string or a
+    /// Rocket `URI`.
+    pub fn allow<P: IntoEndpointPath>(self, path: P) -> EndpointBuilder {
+        EndpointBuilder {
+            builder: self,
+            path: path.into_endpoint_path(),
+            methods: vec![],
+            origins: None,
+        }
+    }
+
+    /// Adds all the endpoints of `group`.
+    pub fn group(mut self, group: EndpointGroup) -> Self {
+        self.endpoints.extend(group.endpoints());
+        self
+    }
+
+    /// Checks the declared endpoints and builds the fairing, failing like
+    /// `CORS::try_new`.
+    pub fn finish(self) -> Result<CORS, TemplateError> {
+        let mut cors = CORS::try_new(self.endpoints)?;
+        for (endpoint, origins) in self.origins {
+            let policy = EndpointPolicy::new().allowed_origins(origins);
+            cors.endpoint_policies[endpoint].merge(&policy);
+        }
+        Ok(cors)
+    }
+}
+
+/// Declares one endpoint, created by `Builder::allow`.
+/// Endpoints without any method allow `GET`.
+#[derive(Clone, Debug)]
+pub struct EndpointBuilder {
+    builder: Builder,
+    path: String,
+    methods: Vec<Method>,
+    origins: Option<AllowedOrigins>,
+}
+
+impl EndpointBuilder {
+    /// Allows `method` on this endpoint.
+    pub fn method(mut self, method: Method) -> Self {
+        add_methods(&mut self.methods, &[method]);
+        self
+    }
+
+    /// Allows all of `methods` on this endpoint.
+    pub fn methods(self, methods: &[Method]) -> Self {
+        methods.iter().fold(self, |endpoint, method| endpoint.method(*method))
+    }
+
+    /// Only allows `origins` on this endpoint, instead of the origins of the
+    /// fairing, like `AllowedOrigins::some`. Other endpoints with the same
+    /// path keep their own origins.
+    pub fn origins(mut self, origins: &[&str]) -> Self {
+        self.origins = Some(AllowedOrigins::some(origins));
+        self
+    }
+
+    /// Allows any origin on this endpoint, whatever the origins of the
+    /// fairing.
+    pub fn any_origin(mut self) -> Self {
+        self.origins = Some(AllowedOrigins::All);
+        self
+    }
+
+    /// Ends this endpoint, to declare another one.
+    pub fn and(self) -> Builder {
+        let mut builder = self.builder;
+        let methods = if self.methods.is_empty() {
+            vec![Method::Get]
+        } else {
+            self.methods
+        };
+        if let Some(origins) = self.origins {
+            let endpoint = builder.endpoints.len();
+            builder.origins.push((endpoint, origins));
+        }
+        builder.endpoints.push((methods, self.path));
+        builder
+    }
+
+    /// Ends this endpoint and starts declaring another one for `path`.
+    pub fn allow<P: IntoEndpointPath>(self, path: P) -> EndpointBuilder {
+        self.and().allow(path)
+    }
+
+    /// Ends this endpoint, checks all the declared ones and builds the
+    /// fairing.
+    pub fn finish(self) -> Result<CORS, TemplateError> {
+        self.and().finish()
+    }
+}
+
+/// Endpoints under a shared base path, allowing a shared set of methods.
+/// Endpoints left without any method allow `GET`.
+#[derive(Clone, Debug)]
+pub struct EndpointGroup {
+    base: String,
+    methods: Vec<Method>,
+    children: Vec<(String, Vec<Method>)>,
+}
+
+impl EndpointGroup {
+    /// Creates a group of endpoints under `base`, like "/api/v1".
+    pub fn base<P: IntoEndpointPath>(base: P) -> Self {
+        EndpointGroup {
+            base: base.into_endpoint_path(),
+            methods: vec![],
+            children: vec![],
+        }
+    }
+
+    /// Allows `methods` on every endpoint of the group.
+    pub fn methods(mut self, methods: &[Method]) -> Self {
+        add_methods(&mut self.methods, methods);
+        self
+    }
+
+    /// Adds an endpoint for `path`, relative to the base path.
+    pub fn path<P: IntoEndpointPath>(self, path: P) -> Self {
+        self.path_with(path, &[])
+    }
+
+    /// Adds an endpoint for `path`, relative to the base path, that also
+    /// allows `methods`.
+    pub fn path_with<P: IntoEndpointPath>(mut self, path: P, methods: &[Method]) -> Self {
+        self.children.push((path.into_endpoint_path(), methods.to_vec()));
+        self
+    }
+
+    /// The endpoints of the group, with full paths.
+    pub fn endpoints(&self) -> Vec<CORSEndpoint> {
+        self.children
+            .iter()
+            .map(|&(ref path, ref methods)| {
+                let mut all = self.methods.clone();
+                add_methods(&mut all, methods);
+                if all.is_empty() {
+                    all.push(Method::Get);
+                }
+                (all, join_paths(&self.base, path))
+            })
+            .collect()
+    }
+}
+
+fn add_methods(methods: &mut Vec<Method>, added: &[Method]) {
+    for method in added {
+        if !methods.contains(method) {
+            methods.push(*method);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EndpointGroup;
+    use {AllowedOrigins, Decision, RequestMeta, CORS};
+    use rocket::http::Method;
+    use rocket::http::uri::URI;
+    use template::TemplateError;
+
+    #[test]
+    fn build_endpoints() {
+        let cors = CORS::build()
+            .allow("/api/users/:id")
+            .methods(&[Method::Get, Method::Put])
+            .method(Method::Get)
+            .and()
+            .allow("/health")
+            .finish()
+            .unwrap();
+        assert_eq!(
+            cors.endpoints(),
+            &[
+                (vec![Method::Get, Method::Put], "/api/users/:id".to_owned()),
+                (vec![Method::Get], "/health".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_from_uri() {
+        let cors = CORS::build()
+            .allow(URI::new("/api/users/<id>"))
+            .finish()
+            .unwrap();
+        assert!(cors.allows(Method::Get, "/api/users/42"));
+    }
+
+    #[test]
+    fn build_invalid_path() {
+        let result = CORS::build().allow("/api//users").finish();
+        assert_eq!(result.err(), Some(TemplateError::EmptySegment(5)));
+    }
+
+    #[test]
+    fn endpoint_origins() {
+        let cors = CORS::build()
+            .allow("/api/users/:id")
+            .methods(&[Method::Get, Method::Put])
+            .origins(&["https://app.example.com"])
+            .and()
+            .allow("/health")
+            .any_origin()
+            .and()
+            .allow("/api/items")
+            .finish()
+            .unwrap()
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]));
+        let users = cors.allowed_origins_of(Some(0));
+        assert!(users.allows(Some("https://app.example.com")));
+        assert!(!users.allows(Some("https://example.com")));
+        assert_eq!(cors.allowed_origins_of(Some(1)), &AllowedOrigins::All);
+        let items = cors.allowed_origins_of(Some(2));
+        assert!(items.allows(Some("https://example.com")));
+        assert!(!items.allows(Some("https://app.example.com")));
+    }
+
+    #[test]
+    fn origins_of_shared_paths() {
+        let cors = CORS::build()
+            .allow("/x")
+            .method(Method::Get)
+            .any_origin()
+            .and()
+            .allow("/x")
+            .method(Method::Delete)
+            .origins(&["https://admin.example.com"])
+            .finish()
+            .unwrap()
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]));
+        let get = RequestMeta {
+            origin: Some("https://example.com"),
+            ..RequestMeta::new(Method::Get, "/x")
+        };
+        assert_eq!(cors.evaluate_meta(&get), Decision::Allowed(0));
+        let delete = RequestMeta {
+            method: Method::Delete,
+            ..get
+        };
+        assert_eq!(cors.evaluate_meta(&delete), Decision::OriginNotAllowed);
+        let admin = RequestMeta {
+            origin: Some("https://admin.example.com"),
+            ..delete
+        };
+        assert_eq!(cors.evaluate_meta(&admin), Decision::Allowed(1));
+    }
+
+    #[test]
+    fn groups() {
+        let group = EndpointGroup::base("/api/v1/")
+            .methods(&[Method::Get])
+            .path("/users")
+            .path_with("users/:id", &[Method::Put, Method::Get])
+            .path("/");
+        let cors = CORS::build()
+            .allow("/health")
+            .and()
+            .group(group)
+            .finish()
+            .unwrap();
+        assert_eq!(
+            cors.endpoints(),
+            &[
+                (vec![Method::Get], "/health".to_owned()),
+                (vec![Method::Get], "/api/v1/users".to_owned()),
+                (vec![Method::Get, Method::Put], "/api/v1/users/:id".to_owned()),
+                (vec![Method::Get], "/api/v1".to_owned()),
+            ]
+        );
+        assert_eq!(
+            EndpointGroup::base("/").path("/").endpoints(),
+            vec![(vec![Method::Get], "/".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The differences between two policies, to log what changes when one
+//! replaces another.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::{AllowedOrigins, CORS};
+//!
+//! let current = cors!("/api/:item" => Method::Get)
+//!     .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]));
+//! let next = cors!("/api/:item" => Method::Get, Method::Put)
+//!     .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]));
+//! assert_eq!(
+//!     current.diff(&next).to_string(),
+//!     "+ origin https://admin.example.com\n\
+//!      - origin https://app.example.com\n\
+//!      ~ endpoint /api/:item: GET -> GET, PUT\n"
+//! );
+//! # }
+//! ```
+
+use {AllowedHeaders, Fallback, CORS};
+use policy::EndpointPolicy;
+use rocket::http::Method;
+use std::fmt;
+use std::time::Duration;
+
+/// A difference between two policies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// An origin is allowed, "*" for any origin.
+    OriginAdded(String),
+    /// An origin isn't allowed anymore.
+    OriginRemoved(String),
+    /// An origin is blocked.
+    BlockedOriginAdded(String),
+    /// An origin isn't blocked anymore.
+    BlockedOriginRemoved(String),
+    /// An endpoint path is configured, with these methods.
+    EndpointAdded(String, Vec<Method>),
+    /// An endpoint path isn't configured anymore.
+    EndpointRemoved(String),
+    /// The methods of an endpoint path changed, from the first ones to the
+    /// second ones.
+    MethodsChanged(String, Vec<Method>, Vec<Method>),
+    /// The policy of an endpoint path changed, from the first one to the
+    /// second one.
+    PolicyChanged(String, EndpointPolicy, EndpointPolicy),
+    /// Credentials are allowed or not anymore.
+    CredentialsChanged(bool),
+    /// Another setting, named like "require origin", is enabled or not
+    /// anymore.
+    SettingChanged(&'static str, bool),
+    /// The allowed request headers changed.
+    AllowedHeadersChanged(AllowedHeaders, AllowedHeaders),
+    /// The handling of paths no endpoint matches changed.
+    FallbackChanged(Fallback, Fallback),
+    /// The max age of preflights changed.
+    MaxAgeChanged(Option<Duration>, Option<Duration>),
+    /// A header is exposed.
+    ExposedHeaderAdded(String),
+    /// A header isn't exposed anymore.
+    ExposedHeaderRemoved(String),
+}
+
+fn methods(methods: &[Method]) -> String {
+    let methods: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+    methods.join(", ")
+}
+
+fn max_age(max_age: Option<Duration>) -> String {
+    match max_age {
+        Some(max_age) => format!("{}s", max_age.as_secs()),
+        None => "none".to_owned(),
+    }
+}
+
+fn allowed_headers(headers: &AllowedHeaders) -> String {
+    match *headers {
+        AllowedHeaders::Some(ref headers) => format!("[{}]", headers.join(", ")),
+        AllowedHeaders::All => "any".to_owned(),
+        AllowedHeaders::Echo => "echoed".to_owned(),
+    }
+}
+
+fn fallback(fallback: &Fallback) -> String {
+    match *fallback {
+        Fallback::Deny => "deny".to_owned(),
+        Fallback::Allow(ref allowed) => format!("allow {}", methods(allowed.as_slice())),
+    }
+}
+
+/// The settings `policy` replaces, like "origins [https://a.com],
+/// credentials true".
+fn policy(policy: &EndpointPolicy) -> String {
+    let mut settings = vec![];
+    if let Some(ref origins) = policy.allowed_origins {
+        settings.push(format!("origins [{}]", origins.entries().join(", ")));
+    }
+    if let Some(ref headers) = policy.allowed_headers {
+        settings.push(format!("headers {}", allowed_headers(headers)));
+    }
+    if let Some(ref exposed) = policy.expose_headers {
+        settings.push(format!("exposed headers [{}]", exposed.join(", ")));
+    }
+    if policy.max_age.is_some() {
+        settings.push(format!("max age {}", max_age(policy.max_age)));
+    }
+    if let Some(allow) = policy.allow_credentials {
+        settings.push(format!("credentials {}", allow));
+    }
+    if policy.switch.is_some() {
+        settings.push("switch".to_owned());
+    }
+    if settings.is_empty() {
+        return "none".to_owned();
+    }
+    settings.join(", ")
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::OriginAdded(ref origin) => write!(f, "+ origin {}", origin),
+            Change::OriginRemoved(ref origin) => write!(f, "- origin {}", origin),
+            Change::BlockedOriginAdded(ref origin) => write!(f, "+ blocked origin {}", origin),
+            Change::BlockedOriginRemoved(ref origin) => write!(f, "- blocked origin {}", origin),
+            Change::EndpointAdded(ref path, ref added) => {
+                write!(f, "+ endpoint {} ({})", path, methods(added))
+            }
+            Change::EndpointRemoved(ref path) => write!(f, "- endpoint {}", path),
+            Change::MethodsChanged(ref path, ref before, ref after) => write!(
+                f,
+                "~ endpoint {}: {} -> {}",
+                path,
+                methods(before),
+                methods(after)
+            ),
+            Change::PolicyChanged(ref path, ref before, ref after) => write!(
+                f,
+                "~ policy {}: {} -> {}",
+                path,
+                policy(before),
+                policy(after)
+            ),
+            Change::CredentialsChanged(allowed) => {
+                write!(f, "~ credentials: {} -> {}", !allowed, allowed)
+            }
+            Change::SettingChanged(name, enabled) => {
+                write!(f, "~ {}: {} -> {}", name, !enabled, enabled)
+            }
+            Change::AllowedHeadersChanged(ref before, ref after) => write!(
+                f,
+                "~ allowed headers: {} -> {}",
+                allowed_headers(before),
+                allowed_headers(after)
+            ),
+            Change::FallbackChanged(ref before, ref after) => {
+                write!(f, "~ fallback: {} -> {}", fallback(before), fallback(after))
+            }
+            Change::MaxAgeChanged(before, after) => {
+                write!(f, "~ max age: {} -> {}", max_age(before), max_age(after))
+            }
+            Change::ExposedHeaderAdded(ref header) => write!(f, "+ exposed header {}", header),
+            Change::ExposedHeaderRemoved(ref header) => write!(f, "- exposed header {}", header),
+        }
+    }
+}
+
+/// The changes from one policy to another, found by `CORS::diff`. Its
+/// display has a line per change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyDiff {
+    /// The changes, origins first, then endpoints and other settings.
+    pub changes: Vec<Change>,
+}
+
+impl PolicyDiff {
+    /// Returns true if the policies have the same settings.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for PolicyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The methods and policy of each endpoint path, in the order they are
+/// configured.
+fn endpoint_methods(cors: &CORS) -> Vec<(&str, Vec<Method>, &EndpointPolicy)> {
+    let mut endpoints: Vec<(&str, Vec<Method>, &EndpointPolicy)> = vec![];
+    for (&(ref methods, ref path), policy) in cors.endpoints().iter().zip(&cors.endpoint_policies)
+    {
+        let path = path.as_str();
+        let index = match endpoints.iter().position(|&(p, _, _)| p == path) {
+            Some(index) => index,
+            None => {
+                endpoints.push((path, vec![], policy));
+                endpoints.len() - 1
+            }
+        };
+        for method in methods {
+            if !endpoints[index].1.contains(method) {
+                endpoints[index].1.push(*method);
+            }
+        }
+    }
+    endpoints
+}
+
+/// Pushes the entries of `after` missing from `before` with `added`, then
+/// those of `before` missing from `after` with `removed`.
+fn list_changes<A, R>(
+    changes: &mut Vec<Change>,
+    before: &[String],
+    after: &[String],
+    added: A,
+    removed: R,
+) where
+    A: Fn(String) -> Change,
+    R: Fn(String) -> Change,
+{
+    for entry in after.iter().filter(|entry| !before.contains(*entry)) {
+        changes.push(added(entry.clone()));
+    }
+    for entry in before.iter().filter(|entry| !after.contains(*entry)) {
+        changes.push(removed(entry.clone()));
+    }
+}
+
+pub(crate) fn diff(before: &CORS, after: &CORS) -> PolicyDiff {
+    let mut changes = vec![];
+    list_changes(
+        &mut changes,
+        &before.allowed_origins.entries(),
+        &after.allowed_origins.entries(),
+        Change::OriginAdded,
+        Change::OriginRemoved,
+    );
+    list_changes(
+        &mut changes,
+        before.blocked_origins.entries(),
+        after.blocked_origins.entries(),
+        Change::BlockedOriginAdded,
+        Change::BlockedOriginRemoved,
+    );
+
+    let (old, new) = (endpoint_methods(before), endpoint_methods(after));
+    let default_policy = EndpointPolicy::new();
+    for &(path, ref methods, policy) in &new {
+        let previous = old.iter().find(|&&(p, _, _)| p == path);
+        match previous {
+            None => changes.push(Change::EndpointAdded(path.to_owned(), methods.clone())),
+            Some(&(_, ref previous, _)) if previous != methods => changes.push(
+                Change::MethodsChanged(path.to_owned(), previous.clone(), methods.clone()),
+            ),
+            Some(_) => {}
+        }
+        let previous_policy = previous.map_or(&default_policy, |&(_, _, policy)| policy);
+        if previous_policy != policy {
+            changes.push(Change::PolicyChanged(
+                path.to_owned(),
+                previous_policy.clone(),
+                policy.clone(),
+            ));
+        }
+    }
+    for &(path, _, _) in &old {
+        if !new.iter().any(|&(p, _, _)| p == path) {
+            changes.push(Change::EndpointRemoved(path.to_owned()));
+        }
+    }
+
+    if before.allow_credentials != after.allow_credentials {
+        changes.push(Change::CredentialsChanged(after.allow_credentials));
+    }
+    let settings = [
+        ("null origin", before.allow_null_origin, after.allow_null_origin),
+        ("require origin", before.require_origin, after.require_origin),
+        ("reflect origin", before.reflect_origin, after.reflect_origin),
+        (
+            "reject disallowed methods",
+            before.reject_disallowed_methods,
+            after.reject_disallowed_methods,
+        ),
+        (
+            "reflect requested method",
+            before.reflect_requested_method,
+            after.reflect_requested_method,
+        ),
+        ("head with get", before.head_with_get, after.head_with_get),
+    ];
+    for &(name, enabled, now_enabled) in &settings {
+        if enabled != now_enabled {
+            changes.push(Change::SettingChanged(name, now_enabled));
+        }
+    }
+    if before.allowed_headers != after.allowed_headers {
+        changes.push(Change::AllowedHeadersChanged(
+            before.allowed_headers.clone(),
+            after.allowed_headers.clone(),
+        ));
+    }
+    if before.fallback != after.fallback {
+        changes.push(Change::FallbackChanged(
+            before.fallback.clone(),
+            after.fallback.clone(),
+        ));
+    }
+    if before.max_age != after.max_age {
+        changes.push(Change::MaxAgeChanged(before.max_age, after.max_age));
+    }
+    list_changes(
+        &mut changes,
+        &before.expose_headers,
+        &after.expose_headers,
+        Change::ExposedHeaderAdded,
+        Change::ExposedHeaderRemoved,
+    );
+    PolicyDiff { changes: changes }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Change;
+    use {AllowedHeaders, AllowedOrigins, Fallback, MethodSet, CORS};
+    use policy::EndpointPolicy;
+    use rocket::http::Method;
+    use std::time::Duration;
+
+    #[test]
+    fn lists_changes() {
+        let before = cors!("/api/:item" => Method::Get; "/legacy" => Method::Get)
+            .expose_headers(&["X-Total"]);
+        let after = cors!("/api/:item" => Method::Get; "/admin" => Method::Get, Method::Post)
+            .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]))
+            .allow_credentials(true)
+            .max_age(Duration::from_secs(600))
+            .expose_headers(&["X-Total"]);
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::OriginAdded("https://admin.example.com".to_owned()),
+                Change::OriginRemoved("*".to_owned()),
+                Change::EndpointAdded("/admin".to_owned(), vec![Method::Get, Method::Post]),
+                Change::EndpointRemoved("/legacy".to_owned()),
+                Change::CredentialsChanged(true),
+                Change::MaxAgeChanged(None, Some(Duration::from_secs(600))),
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "+ origin https://admin.example.com\n\
+             - origin *\n\
+             + endpoint /admin (GET, POST)\n\
+             - endpoint /legacy\n\
+             ~ credentials: false -> true\n\
+             ~ max age: none -> 600s\n"
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn lists_security_settings() {
+        let before = cors!("/api/:item" => Method::Get; "/admin" => Method::Get);
+        let after = cors!("/api/:item" => Method::Get; "/admin" => Method::Get)
+            .blocked_origins(&["https://evil.com"])
+            .allow_null_origin(true)
+            .require_origin(false)
+            .reject_disallowed_methods(true)
+            .allowed_headers(AllowedHeaders::All)
+            .fallback(Fallback::Allow(MethodSet::empty().with(Method::Get)))
+            .policy_for(
+                "/admin",
+                EndpointPolicy::new()
+                    .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]))
+                    .allow_credentials(true),
+            );
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.to_string(),
+            "+ blocked origin https://evil.com\n\
+             ~ policy /admin: none -> origins [https://admin.example.com], credentials true\n\
+             ~ null origin: false -> true\n\
+             ~ require origin: true -> false\n\
+             ~ reject disallowed methods: false -> true\n\
+             ~ allowed headers: [accept, accept-language, authorization, content-type] -> any\n\
+             ~ fallback: deny -> allow GET\n"
+        );
+        assert_eq!(
+            diff.changes[1],
+            Change::PolicyChanged(
+                "/admin".to_owned(),
+                EndpointPolicy::new(),
+                after.endpoint_policies[1].clone()
+            )
+        );
+        assert!(!after.diff(&before).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Routes opting out of CORS from their handler, even when an endpoint of
+//! the fairing matches their path.
+//!
+//! Exempt responses are marked with a header that every CORS fairing leaves
+//! alone, and that the `Exemptions` fairing removes. Attach it once, after
+//! the CORS fairings: response fairings run in the order they are attached.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::exempt::{Exempt, Exemptions};
+//!
+//! // Mounted as `#[get("/api/health")]`, under a `/api/:resource` endpoint.
+//! fn health() -> Exempt<&'static str> {
+//!     Exempt("OK")
+//! }
+//!
+//! # fn main() {
+//! let rocket = rocket::ignite()
+//!     .attach(cors!("/api/:resource" => Method::Get))
+//!     .attach(Exemptions);
+//! # health();
+//! # }
+//! ```
+
+use rocket::{Request, Response, Rocket, State};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::response::{self, Responder};
+
+/// The header marking exempt responses. `Exemptions` removes it.
+pub(crate) const EXEMPT_HEADER: &str = "X-Rocket-Cors-Exempt";
+
+/// Managed by `Exemptions`, so that responses are only marked when the
+/// marker is removed before they are sent.
+struct Marking;
+
+/// The fairing removing the marker of exempt responses, once the CORS
+/// fairings have seen it. Without it, `Exempt` has no effect.
+#[derive(Clone, Copy, Debug)]
+pub struct Exemptions;
+
+impl Fairing for Exemptions {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS exemptions",
+            kind: Kind::Attach | Kind::Response,
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        Ok(rocket.manage(Marking))
+    }
+
+    fn on_response(&self, _: &Request, response: &mut Response) {
+        response.remove_header(EXEMPT_HEADER);
+    }
+}
+
+/// A responder wrapping the one of a route, so that the CORS fairings neither
+/// add CORS headers to its responses nor answer its preflights, provided
+/// `Exemptions` is attached.
+///
+/// Preflights are only left alone if the route also answers OPTIONS with
+/// an `Exempt` responder.
+#[derive(Debug)]
+pub struct Exempt<R>(pub R);
+
+impl<'r, R: Responder<'r>> Responder<'r> for Exempt<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = self.0.respond_to(request)?;
+        if request.guard::<State<Marking>>().is_success() {
+            response.set_raw_header(EXEMPT_HEADER, "1");
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Exempt, Exemptions, EXEMPT_HEADER};
+    use {AllowedOrigins, CORS};
+    use rocket;
+    use rocket::http::{Header, Method, Status};
+    use rocket::local::Client;
+
+    #[get("/api/health")]
+    fn health() -> Exempt<&'static str> {
+        Exempt("OK")
+    }
+
+    #[options("/api/health")]
+    fn health_options() -> Exempt<Status> {
+        Exempt(Status::NoContent)
+    }
+
+    #[get("/api/items")]
+    fn items() -> &'static str {
+        "[]"
+    }
+
+    #[test]
+    fn exempt_routes() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![health, health_options, items])
+            .attach(cors!("/api/:resource" => Method::Get))
+            .attach(Exemptions);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let origin = Header::new("Origin", "https://example.com");
+
+        let response = client.get("/api/health").header(origin.clone()).dispatch();
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+        assert!(!response.headers().contains(EXEMPT_HEADER));
+
+        let response = client
+            .options("/api/health")
+            .header(origin.clone())
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+
+        let response = client.get("/api/items").header(origin).dispatch();
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn several_fairings() {
+        let cors2 = cors!("/api/health" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]));
+        let rocket = rocket::ignite()
+            .mount("/", routes![health])
+            .attach(cors!("/api/:resource" => Method::Get))
+            .attach(cors2)
+            .attach(Exemptions);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client
+            .get("/api/health")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+        assert!(!response.headers().contains("Vary"));
+        assert!(!response.headers().contains(EXEMPT_HEADER));
+    }
+
+    #[test]
+    fn unmarked_without_exemptions() {
+        let rocket = rocket::ignite().mount("/", routes![health]);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client.get("/api/health").dispatch();
+        assert!(!response.headers().contains(EXEMPT_HEADER));
+    }
+}
@@ -0,0 +1,142 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The CORS access granted to a request, and its rendering as response
+//! headers.
+
+use rocket::http::{Header, Method};
+use std::vec;
+
+/// The CORS access granted to one request.
+/// Converts into the response headers granting it, in a stable order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grant {
+    /// The value of `Access-Control-Allow-Origin`.
+    pub allow_origin: String,
+    /// Whether `Access-Control-Allow-Credentials: true` is sent.
+    pub allow_credentials: bool,
+    /// The value of `Access-Control-Max-Age`, in seconds, if any.
+    pub max_age: Option<u64>,
+    /// The request headers listed in `Vary`, which is left out when empty.
+    pub vary: Vec<String>,
+    /// The request headers listed in `Access-Control-Allow-Headers`, which is
+    /// left out when empty.
+    pub allow_headers: Vec<String>,
+    /// The methods listed in `Access-Control-Allow-Methods`.
+    pub allow_methods: Vec<Method>,
+    /// The response headers listed in `Access-Control-Expose-Headers`, which
+    /// is left out when empty.
+    pub expose_headers: Vec<String>,
+    /// Other headers added as they are.
+    pub extra_headers: Vec<(String, String)>,
+}
+
+/// How a grant is rendered. Browsers take `*` literally in the headers of
+/// credentialed responses, so those never carry wildcards and list exact
+/// values instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Requests without credentials, which can be granted to `*`.
+    Anonymous,
+    /// Requests carrying credentials, granted to their own origin.
+    Credentialed,
+}
+
+impl Grant {
+    /// The response headers granting this access.
+    pub fn headers(&self) -> Vec<Header<'static>> {
+        let methods: Vec<&str> = self.allow_methods.iter().map(|m| m.as_str()).collect();
+        let mut headers = vec![
+            Header::new("Access-Control-Allow-Origin", self.allow_origin.clone()),
+        ];
+        if !self.allow_headers.is_empty() {
+            headers.push(Header::new(
+                "Access-Control-Allow-Headers",
+                self.allow_headers.join(", "),
+            ));
+        }
+        headers.push(Header::new("Access-Control-Allow-Methods", methods.join(", ")));
+        if self.allow_credentials {
+            headers.push(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+        if let Some(max_age) = self.max_age {
+            headers.push(Header::new("Access-Control-Max-Age", max_age.to_string()));
+        }
+        if !self.vary.is_empty() {
+            headers.push(Header::new("Vary", self.vary.join(", ")));
+        }
+        if !self.expose_headers.is_empty() {
+            headers.push(Header::new(
+                "Access-Control-Expose-Headers",
+                self.expose_headers.join(", "),
+            ));
+        }
+        for &(ref name, ref value) in &self.extra_headers {
+            headers.push(Header::new(name.clone(), value.clone()));
+        }
+        headers
+    }
+}
+
+impl From<Grant> for Vec<Header<'static>> {
+    fn from(grant: Grant) -> Self {
+        grant.headers()
+    }
+}
+
+impl<'a> From<&'a Grant> for Vec<Header<'static>> {
+    fn from(grant: &'a Grant) -> Self {
+        grant.headers()
+    }
+}
+
+impl IntoIterator for Grant {
+    type Item = Header<'static>;
+    type IntoIter = vec::IntoIter<Header<'static>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.headers().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Grant {
+    type Item = Header<'static>;
+    type IntoIter = vec::IntoIter<Header<'static>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.headers().into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Grant;
+    use rocket::http::{Header, Method};
+
+    #[test]
+    fn renders_headers() {
+        let grant = Grant {
+            allow_origin: "https://example.com".to_owned(),
+            allow_credentials: true,
+            max_age: Some(600),
+            vary: vec!["Origin".to_owned()],
+            allow_headers: vec!["accept".to_owned(), "content-type".to_owned()],
+            allow_methods: vec![Method::Get, Method::Head],
+            expose_headers: vec!["ETag".to_owned()],
+            extra_headers: vec![("Timing-Allow-Origin".to_owned(), "*".to_owned())],
+        };
+        let expected = vec![
+            Header::new("Access-Control-Allow-Origin", "https://example.com"),
+            Header::new("Access-Control-Allow-Headers", "accept, content-type"),
+            Header::new("Access-Control-Allow-Methods", "GET, HEAD"),
+            Header::new("Access-Control-Allow-Credentials", "true"),
+            Header::new("Access-Control-Max-Age", "600"),
+            Header::new("Vary", "Origin"),
+            Header::new("Access-Control-Expose-Headers", "ETag"),
+            Header::new("Timing-Allow-Origin", "*"),
+        ];
+        assert_eq!(Vec::from(&grant), expected);
+        assert_eq!(grant.into_iter().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Request headers granted to CORS requests.
+//!
+//! # Example:
+//! ```
+//! use rocket_cors::AllowedHeaders;
+//! use rocket_cors::headers::RequestHeader;
+//!
+//! let headers = AllowedHeaders::known(&[
+//!     RequestHeader::Authorization,
+//!     RequestHeader::ContentType,
+//!     RequestHeader::custom("X-Api-Key").expect("valid header name"),
+//! ]);
+//! # assert!(headers.allows("x-api-key"));
+//! ```
+
+use ALLOWED_HEADERS;
+use std::error::Error;
+use std::fmt;
+
+/// The request headers granted in `Access-Control-Allow-Headers`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AllowedHeaders {
+    /// Only these headers, compared case-insensitively. The default is
+    /// "accept", "accept-language", "authorization" and "content-type".
+    Some(Vec<String>),
+    /// Any header. Anonymous requests get `*`, and credentialed preflights,
+    /// for which browsers take `*` literally, get the headers they ask for.
+    All,
+    /// Any header, always granted by echoing the
+    /// `Access-Control-Request-Headers` of preflights as they are.
+    Echo,
+}
+
+impl AllowedHeaders {
+    /// Only allows `headers`.
+    pub fn some(headers: &[&str]) -> Self {
+        AllowedHeaders::Some(headers.iter().map(|h| (*h).to_owned()).collect())
+    }
+
+    /// Only allows `headers`, named without the risk of a typo.
+    pub fn known(headers: &[RequestHeader]) -> Self {
+        AllowedHeaders::Some(headers.iter().map(|h| h.name().to_owned()).collect())
+    }
+
+    /// Returns true if requests can use the `header` request header.
+    pub fn allows(&self, header: &str) -> bool {
+        match *self {
+            AllowedHeaders::Some(ref headers) => headers
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(header)),
+            AllowedHeaders::All | AllowedHeaders::Echo => true,
+        }
+    }
+}
+
+impl Default for AllowedHeaders {
+    fn default() -> Self {
+        AllowedHeaders::some(ALLOWED_HEADERS)
+    }
+}
+
+/// A request header commonly allowed, or another one checked when it is
+/// built with `RequestHeader::custom`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestHeader {
+    /// `Accept`.
+    Accept,
+    /// `Accept-Language`.
+    AcceptLanguage,
+    /// `Authorization`.
+    Authorization,
+    /// `Content-Type`.
+    ContentType,
+    /// `X-Requested-With`.
+    XRequestedWith,
+    /// Another header.
+    Custom(CustomHeader),
+}
+
+/// The name of a request header that isn't one of the common ones, built
+/// with `RequestHeader::custom`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomHeader(String);
+
+/// The error returned by `RequestHeader::custom` for a name that isn't a
+/// valid header name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidHeaderName(pub String);
+
+impl fmt::Display for InvalidHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid header name `{}`", self.0)
+    }
+}
+
+impl Error for InvalidHeaderName {
+    fn description(&self) -> &str {
+        "invalid header name"
+    }
+}
+
+impl RequestHeader {
+    /// A header other than the common ones, whose name must be an HTTP token
+    /// like "X-Api-Key".
+    pub fn custom<S: Into<String>>(name: S) -> Result<Self, InvalidHeaderName> {
+        let name = name.into();
+        let valid = !name.is_empty() &&
+            name.chars()
+                .all(|c| c.is_ascii() && (c.is_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)));
+        if valid {
+            Ok(RequestHeader::Custom(CustomHeader(name)))
+        } else {
+            Err(InvalidHeaderName(name))
+        }
+    }
+
+    /// The name of the header, lowercase for the common ones.
+    pub fn name(&self) -> &str {
+        match *self {
+            RequestHeader::Accept => "accept",
+            RequestHeader::AcceptLanguage => "accept-language",
+            RequestHeader::Authorization => "authorization",
+            RequestHeader::ContentType => "content-type",
+            RequestHeader::XRequestedWith => "x-requested-with",
+            RequestHeader::Custom(CustomHeader(ref name)) => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AllowedHeaders, InvalidHeaderName, RequestHeader};
+
+    #[test]
+    fn allows() {
+        let headers = AllowedHeaders::default();
+        assert!(headers.allows("Content-Type"));
+        assert!(headers.allows("authorization"));
+        assert!(!headers.allows("X-Requested-With"));
+        assert!(AllowedHeaders::All.allows("X-Requested-With"));
+        assert!(AllowedHeaders::Echo.allows("X-Requested-With"));
+    }
+
+    #[test]
+    fn known_headers() {
+        let headers = AllowedHeaders::known(&[
+            RequestHeader::XRequestedWith,
+            RequestHeader::custom("X-Api-Key").unwrap(),
+        ]);
+        assert_eq!(headers, AllowedHeaders::some(&["x-requested-with", "X-Api-Key"]));
+        assert_eq!(
+            RequestHeader::custom("X Api Key"),
+            Err(InvalidHeaderName("X Api Key".to_owned()))
+        );
+        assert!(RequestHeader::custom("").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Extension points letting applications take part in the fairing's work.
+
+use Decision;
+use rocket::Request;
+use rocket::http::Header;
+
+/// A hook invoked once the fairing has computed the headers of a response,
+/// right before they are written. It can add, change or remove headers.
+///
+/// Hooks run for every request, with an empty header list when no CORS
+/// access is granted; check the decision to act only on allowed requests.
+/// Closures with the same signature as `on_headers` are hooks too.
+pub trait ResponseHook: Send + Sync + 'static {
+    /// Called with the request, the decision taken for it and the headers
+    /// about to be set on the response.
+    fn on_headers(&self, request: &Request, decision: &Decision, headers: &mut Vec<Header<'static>>);
+}
+
+impl<F> ResponseHook for F
+where
+    F: Fn(&Request, &Decision, &mut Vec<Header<'static>>) + Send + Sync + 'static,
+{
+    fn on_headers(&self, request: &Request, decision: &Decision, headers: &mut Vec<Header<'static>>) {
+        self(request, decision, headers)
+    }
+}
+
+/// A response hook adding headers only to the responses granted CORS access,
+/// built with `on_match`.
+pub struct OnMatch<F>(F);
+
+/// Wraps `f` in a response hook called only when the fairing grants CORS
+/// access, to add headers computed from the request like tracing ones.
+/// Constant headers can simply be added with `CORS::extra_header`.
+pub fn on_match<F>(f: F) -> OnMatch<F>
+where
+    F: Fn(&Request, &mut Vec<Header<'static>>) + Send + Sync + 'static,
+{
+    OnMatch(f)
+}
+
+impl<F> ResponseHook for OnMatch<F>
+where
+    F: Fn(&Request, &mut Vec<Header<'static>>) + Send + Sync + 'static,
+{
+    fn on_headers(&self, request: &Request, decision: &Decision, headers: &mut Vec<Header<'static>>) {
+        if decision.is_allowed() {
+            (self.0)(request, headers)
+        }
+    }
+}
+
+/// Decides at request time whether an origin gets CORS access, for instance
+/// by looking it up in application state with `request.guard()`.
+///
+/// It is only consulted for requests the policy would otherwise allow, and
+/// requests without an `Origin` header are denied.
+/// Closures with the same signature as `allows` are validators too.
+pub trait OriginValidator: Send + Sync + 'static {
+    /// Returns true if `origin` is allowed for `request`.
+    fn allows(&self, origin: &str, request: &Request) -> bool;
+}
+
+impl<F> OriginValidator for F
+where
+    F: Fn(&str, &Request) -> bool + Send + Sync + 'static,
+{
+    fn allows(&self, origin: &str, request: &Request) -> bool {
+        self(origin, request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::on_match;
+    use {Decision, CORS};
+    use rocket::{self, Request};
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn response_hook() {
+        let cors = cors!("/endpoint" => Method::Get).response_hook(
+            |_: &Request, decision: &Decision, headers: &mut Vec<Header<'static>>| {
+                if decision.is_allowed() {
+                    headers.push(Header::new("Deprecation", "true"));
+                }
+            },
+        );
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Deprecation"), Some("true"));
+
+        let response = client
+            .get("/other")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Deprecation"), None);
+    }
+
+    #[test]
+    fn match_hook() {
+        let cors = cors!("/endpoint" => Method::Get).response_hook(on_match(
+            |request: &Request, headers: &mut Vec<Header<'static>>| {
+                let path = request.uri().path().to_owned();
+                headers.push(Header::new("X-Cors-Path", path));
+            },
+        ));
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("X-Cors-Path"), Some("/endpoint"));
+
+        let response = client
+            .get("/other")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("X-Cors-Path"), None);
+    }
+
+    #[test]
+    fn origin_validator() {
+        let cors = cors!("/endpoint" => Method::Get).origin_validator(
+            |origin: &str, _: &Request| origin.ends_with(".customer.com"),
+        );
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://acme.customer.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://acme.customer.com")
+        );
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://evil.com"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+    }
+}
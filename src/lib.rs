@@ -24,17 +24,78 @@
 #![plugin(rocket_codegen)]
 
 extern crate hyper;
+extern crate idna;
+#[cfg(feature = "regex")]
+extern crate regex;
 extern crate rocket;
+#[cfg(feature = "openapi")]
+#[macro_use]
+extern crate serde_json;
 extern crate unicase;
 
-use hyper::header::{AccessControlAllowHeaders, AccessControlAllowMethods, AccessControlAllowOrigin};
-use hyper::method::Method::{Delete, Get, Post, Put};
+pub mod builder;
+pub mod clock;
+pub mod diff;
+pub mod exempt;
+pub mod grant;
+pub mod headers;
+pub mod hooks;
+pub mod lint;
+pub mod matching;
+pub mod methods;
+pub mod migration;
+pub mod monitor;
+pub mod origin;
+pub mod origins;
+pub mod policy;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod preflight;
+pub mod proxy;
+pub mod rates;
+#[cfg(feature = "recording")]
+pub mod recording;
+pub mod selftest;
+pub mod store;
+pub mod switch;
+pub mod template;
+pub mod test_vectors;
+pub mod testing;
+pub mod timing;
+pub mod validation;
+
+pub use headers::AllowedHeaders;
+pub use methods::MethodSet;
+pub use origins::AllowedOrigins;
+
+use clock::{Clock, SystemClock};
+use grant::{Grant, Mode};
+use hooks::{OriginValidator, ResponseHook};
+use origin::OriginRef;
+use origins::OriginList;
+use policy::EndpointPolicy;
+use selftest::{Expectation, SelfTestReport};
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::{Method, Status};
-use rocket::{Request, Response};
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::{Request, Response, Rocket, Route};
 use rocket::response::Body;
 use std::io::Cursor;
-use unicase::UniCase;
+use std::time::{Duration, Instant};
+use template::{join_paths, IntoEndpointPath, PathTemplate, TemplateError};
+
+/// The request headers allowed on CORS requests by default.
+const ALLOWED_HEADERS: &[&str] = &["accept", "accept-language", "authorization", "content-type"];
+
+/// A common set of methods to advertise in `Access-Control-Allow-Methods`
+/// with `CORS::allowed_methods`. See `MethodSet` to build on it.
+pub const DEFAULT_METHODS: &[Method] = &[
+    Method::Get,
+    Method::Head,
+    Method::Post,
+    Method::Put,
+    Method::Patch,
+    Method::Delete,
+];
 
 /// A tuple binding together a set of HTTP methods and a url path.
 pub type CORSEndpoint = (Vec<Method>, String);
@@ -47,8 +108,244 @@ macro_rules! cors {
     )
 }
 
+/// The outcome of evaluating a request against the configured endpoints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The request matched the endpoint at this index in `CORS::endpoints()`.
+    Allowed(usize),
+    /// No endpoint has a path matching the request.
+    NoMatchingPath,
+    /// Some endpoints have a matching path, but none allows the request method.
+    MethodNotAllowed,
+    /// No endpoint has a path matching the request, but the fallback policy
+    /// allows it.
+    Fallback,
+    /// The request would be allowed, but its origin isn't.
+    OriginNotAllowed,
+    /// The request matched the endpoint at this index, whose switch is
+    /// disabled. See the `switch` module.
+    EndpointDisabled(usize),
+    /// The request would be allowed, but it has no valid `Origin` header, so
+    /// it isn't a cross-origin request from a browser.
+    MissingOrigin,
+    /// The request is a preflight with several `Access-Control-Request-Method`
+    /// headers, or one that isn't a method.
+    MalformedPreflight,
+}
+
+impl Decision {
+    /// Returns true if CORS access is granted.
+    pub fn is_allowed(&self) -> bool {
+        match *self {
+            Decision::Allowed(_) | Decision::Fallback => true,
+            _ => false,
+        }
+    }
+
+    /// A short code for this decision, like "method-not-allowed".
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            Decision::Allowed(_) => "allowed",
+            Decision::NoMatchingPath => "no-matching-path",
+            Decision::MethodNotAllowed => "method-not-allowed",
+            Decision::Fallback => "fallback",
+            Decision::OriginNotAllowed => "origin-not-allowed",
+            Decision::EndpointDisabled(_) => "endpoint-disabled",
+            Decision::MissingOrigin => "missing-origin",
+            Decision::MalformedPreflight => "malformed-preflight",
+        }
+    }
+}
+
+/// What the fairing does with requests on paths no endpoint matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    /// No CORS access is granted. This is the default.
+    Deny,
+    /// CORS access is granted to these methods, as if an endpoint matched
+    /// every path.
+    Allow(MethodSet),
+}
+
+/// The casing of the names of the headers set by the fairing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// Mixed case, like "Access-Control-Allow-Origin". This is the default.
+    Standard,
+    /// Lowercase, like "access-control-allow-origin".
+    Lower,
+}
+
+/// How the fairing answers preflights it doesn't grant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreflightFailureMode {
+    /// The response of the application is left as it is, without CORS
+    /// headers. This is the default.
+    PassThrough,
+    /// The response is replaced by a `403 Forbidden` one, with an empty body
+    /// unless `CORS::problem_details` is enabled.
+    Forbidden,
+}
+
+impl Default for PreflightFailureMode {
+    fn default() -> Self {
+        PreflightFailureMode::PassThrough
+    }
+}
+
+impl Default for HeaderCase {
+    fn default() -> Self {
+        HeaderCase::Standard
+    }
+}
+
+impl Default for Fallback {
+    fn default() -> Self {
+        Fallback::Deny
+    }
+}
+
+/// What the fairing looks at in a request, so that it can be evaluated
+/// without building a Rocket `Request`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestMeta<'a> {
+    /// The request method.
+    pub method: Method,
+    /// The request path.
+    pub path: &'a str,
+    /// The value of the `Origin` header.
+    pub origin: Option<&'a str>,
+    /// The method named in `Access-Control-Request-Method`.
+    pub request_method: Option<Method>,
+    /// The value of `Access-Control-Request-Headers`.
+    pub request_headers: Option<&'a str>,
+    /// Whether `Access-Control-Request-Private-Network` is `true`.
+    pub request_private_network: bool,
+    /// Whether `Access-Control-Request-Local-Network` is `true`, the header
+    /// replacing the previous one in the local network access draft.
+    pub request_local_network: bool,
+    /// Whether there are several `Access-Control-Request-Method` headers, or
+    /// one that isn't a method, so that `request_method` is `None`.
+    pub malformed_request_method: bool,
+}
+
+impl<'a> RequestMeta<'a> {
+    /// Describes a request using `method` on `path`, without any header.
+    pub fn new(method: Method, path: &'a str) -> Self {
+        RequestMeta {
+            method: method,
+            path: path,
+            origin: None,
+            request_method: None,
+            request_headers: None,
+            request_private_network: false,
+            request_local_network: false,
+            malformed_request_method: false,
+        }
+    }
+
+    /// Describes a preflight from `origin` asking to use `method` on `path`.
+    pub fn preflight(path: &'a str, origin: &'a str, method: Method) -> Self {
+        RequestMeta {
+            origin: Some(origin),
+            request_method: Some(method),
+            ..RequestMeta::new(Method::Options, path)
+        }
+    }
+
+    /// The method a preflight asks permission for, if this is one.
+    pub fn preflight_method(&self) -> Option<Method> {
+        if self.method == Method::Options {
+            self.request_method
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if this is a CORS preflight: an `OPTIONS` request with
+    /// both `Origin` and `Access-Control-Request-Method` headers. The fairing
+    /// answers those, and leaves the other `OPTIONS` requests to the
+    /// application, like those discovering its capabilities.
+    pub fn is_preflight(&self) -> bool {
+        self.origin.is_some() && self.method == Method::Options &&
+            (self.request_method.is_some() || self.malformed_request_method)
+    }
+}
+
+impl<'a, 'r> From<&'a Request<'r>> for RequestMeta<'a> {
+    fn from(request: &'a Request<'r>) -> Self {
+        let headers = request.headers();
+        let asks = |name: &str| headers.get_one(name).map_or(false, |v| v.trim() == "true");
+        // Which of several methods is asked for is undefined: none is.
+        let mut request_methods = headers.get("Access-Control-Request-Method");
+        let request_method = match (request_methods.next(), request_methods.next()) {
+            (Some(method), None) => method.parse().ok(),
+            _ => None,
+        };
+        RequestMeta {
+            method: request.method(),
+            path: request.uri().path(),
+            origin: headers.get_one("Origin"),
+            request_method: request_method,
+            request_headers: headers.get_one("Access-Control-Request-Headers"),
+            request_private_network: asks("Access-Control-Request-Private-Network"),
+            request_local_network: asks("Access-Control-Request-Local-Network"),
+            malformed_request_method: request_method.is_none() &&
+                headers.contains("Access-Control-Request-Method"),
+        }
+    }
+}
+
 pub struct CORS {
     allowed_endpoints: Vec<CORSEndpoint>,
+    // The parsed path of each endpoint, or None if it is invalid.
+    templates: Vec<Option<PathTemplate>>,
+    extra_headers: Vec<(String, String)>,
+    allow_private_network: bool,
+    allow_local_network: bool,
+    expose_headers: Vec<String>,
+    // The settings of each endpoint replacing those of the fairing.
+    endpoint_policies: Vec<EndpointPolicy>,
+    // The paths given to `policy_for` that no endpoint is declared with.
+    unmatched_policies: Vec<String>,
+    // Whether `policy_for_methods` was used, so that preflights are matched
+    // with the method they ask for.
+    method_policies: bool,
+    max_age: Option<Duration>,
+    reject_disallowed_methods: bool,
+    problem_details: bool,
+    preflight_failure_mode: PreflightFailureMode,
+    preflight_status: Status,
+    advertise_options: bool,
+    reflect_requested_method: bool,
+    head_with_get: bool,
+    // The methods advertised instead of those of the matching endpoints.
+    advertised_methods: Option<MethodSet>,
+    allowed_headers: AllowedHeaders,
+    allowed_origins: AllowedOrigins,
+    blocked_origins: OriginList,
+    // Entries allowed on top of `allowed_origins`, each until an instant.
+    temporary_origins: Vec<(OriginList, Instant)>,
+    clock: Box<Clock>,
+    allow_null_origin: bool,
+    require_origin: bool,
+    skip_non_browser: bool,
+    preflight_rank: Option<isize>,
+    // The expectations of `self_test_on_attach`, checked during attachment.
+    attach_expectations: Vec<(Method, String, Option<String>, Decision)>,
+    allow_credentials: bool,
+    reflect_origin: bool,
+    fallback: Fallback,
+    header_case: HeaderCase,
+    vary_on_cache_key: bool,
+    response_hooks: Vec<Box<ResponseHook>>,
+    origin_validator: Option<Box<OriginValidator>>,
+    timings: Option<timing::Timings>,
+    origin_rates: Option<rates::OriginRates>,
+    #[cfg(feature = "recording")]
+    log: Option<recording::DecisionLog>,
+    #[cfg(feature = "recording")]
+    correlation_header: Option<String>,
 }
 
 impl CORS {
@@ -57,215 +354,2246 @@ impl CORS {
     /// Endpoints containing a variable path part can use ':foo' like in:
     /// '/foo/:bar' for a URL like https://domain.com/foo/123 where 123 is
     /// variable.
+    /// Endpoints with an invalid path never match; use `try_new` to detect
+    /// them.
     pub fn new(endpoints: Vec<CORSEndpoint>) -> Self {
+        let templates = endpoints
+            .iter()
+            .map(|&(_, ref path)| PathTemplate::parse(path).ok())
+            .collect();
+        let endpoint_policies = endpoints.iter().map(|_| EndpointPolicy::new()).collect();
         CORS {
             allowed_endpoints: endpoints,
+            templates: templates,
+            extra_headers: vec![],
+            allow_private_network: false,
+            allow_local_network: false,
+            expose_headers: vec![],
+            endpoint_policies: endpoint_policies,
+            unmatched_policies: vec![],
+            method_policies: false,
+            max_age: None,
+            reject_disallowed_methods: false,
+            problem_details: false,
+            preflight_failure_mode: PreflightFailureMode::default(),
+            preflight_status: Status::Ok,
+            advertise_options: false,
+            reflect_requested_method: false,
+            head_with_get: true,
+            advertised_methods: None,
+            allowed_headers: AllowedHeaders::default(),
+            allowed_origins: AllowedOrigins::All,
+            blocked_origins: OriginList::default(),
+            temporary_origins: vec![],
+            clock: Box::new(SystemClock),
+            allow_null_origin: false,
+            require_origin: true,
+            skip_non_browser: false,
+            preflight_rank: None,
+            attach_expectations: vec![],
+            allow_credentials: false,
+            reflect_origin: false,
+            fallback: Fallback::Deny,
+            header_case: HeaderCase::Standard,
+            vary_on_cache_key: false,
+            response_hooks: vec![],
+            origin_validator: None,
+            timings: None,
+            origin_rates: None,
+            #[cfg(feature = "recording")]
+            log: None,
+            #[cfg(feature = "recording")]
+            correlation_header: None,
         }
     }
 
-    fn is_allowed(&self, request: &Request) -> bool {
-        let mut is_cors_endpoint = false;
-        for endpoint in self.allowed_endpoints.clone() {
-            let (methods, path) = endpoint;
+    /// Creates a new CORS fairing from borrowed endpoints, without the
+    /// `cors!` macro:
+    /// `CORS::from_slice(&[(&[Method::Get, Method::Put], "/api/:user/action")])`.
+    pub fn from_slice(endpoints: &[(&[Method], &str)]) -> Self {
+        CORS::new(
+            endpoints
+                .iter()
+                .map(|&(methods, path)| (methods.to_vec(), path.to_owned()))
+                .collect(),
+        )
+    }
 
-            if !methods.contains(&request.method()) && request.method() != Method::Options {
-                continue;
+    /// Creates a new CORS fairing allowing the method and path of each route,
+    /// so paths can't be mistyped: `CORS::from_routes(routes![a, b])`.
+    /// Paths are taken as declared by the routes, without the base they are
+    /// mounted on. Fails if a route path can't be used as an endpoint path,
+    /// like "/files/<path..>".
+    pub fn from_routes(routes: Vec<Route>) -> Result<Self, TemplateError> {
+        let mut endpoints: Vec<CORSEndpoint> = vec![];
+        for route in routes {
+            let path = route.path.path().to_owned();
+            let position = endpoints.iter().position(|&(_, ref p)| *p == path);
+            match position {
+                Some(index) => if !endpoints[index].0.contains(&route.method) {
+                    endpoints[index].0.push(route.method);
+                },
+                None => endpoints.push((vec![route.method], path)),
             }
+        }
+        CORS::try_new(endpoints)
+    }
 
-            let path: Vec<&str> = if path.starts_with('/') {
-                path[1..].split('/').collect()
-            } else {
-                path[0..].split('/').collect()
-            };
+    /// Starts declaring endpoints with the fluent API of `builder::Builder`.
+    pub fn build() -> builder::Builder {
+        builder::Builder::default()
+    }
 
-            let uri: Vec<&str> = request.uri().segments().collect();
+    /// Like `new`, but fails with the first endpoint path that can't be
+    /// parsed.
+    pub fn try_new(endpoints: Vec<CORSEndpoint>) -> Result<Self, TemplateError> {
+        for &(_, ref path) in &endpoints {
+            PathTemplate::parse(path)?;
+        }
+        Ok(CORS::new(endpoints))
+    }
 
-            if path.len() != uri.len() {
-                continue;
-            }
+    /// The endpoints this fairing enables CORS for.
+    pub fn endpoints(&self) -> &[CORSEndpoint] {
+        &self.allowed_endpoints
+    }
 
-            for i in 0..uri.len() {
-                is_cors_endpoint = false;
-                if uri[i] != path[i] && !path[i].starts_with(':') {
-                    break;
-                }
-                is_cors_endpoint = true;
-            }
-            if is_cors_endpoint {
-                break;
-            }
+    /// Moves all the endpoints under `mount`, the path where their routes are
+    /// mounted, so that `cors!("/users" => Method::Get).scoped("/api")` only
+    /// applies to "/api/users". Other settings are kept.
+    pub fn scoped<P: IntoEndpointPath>(mut self, mount: P) -> Self {
+        let mount = mount.into_endpoint_path();
+        for &mut (_, ref mut path) in &mut self.allowed_endpoints {
+            let joined = join_paths(&mount, path);
+            *path = joined;
         }
-        is_cors_endpoint
+        self.templates = self.allowed_endpoints
+            .iter()
+            .map(|&(_, ref path)| PathTemplate::parse(path).ok())
+            .collect();
+        self
     }
 
-    fn add_headers(response: &mut Response) {
-        response.set_header(AccessControlAllowOrigin::Any);
-        response.set_header(AccessControlAllowHeaders(vec![
-            UniCase(String::from("accept")),
-            UniCase(String::from("accept-language")),
-            UniCase(String::from("authorization")),
-            UniCase(String::from("content-type")),
-        ]));
-        response.set_header(AccessControlAllowMethods(vec![Get, Post, Put, Delete]));
+    /// Adds a header set on every response this fairing grants CORS access
+    /// to, like "X-Frame-Options: DENY".
+    pub fn extra_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.extra_headers.push((name.into(), value.into()));
+        self
     }
-}
 
-impl Fairing for CORS {
-    fn info(&self) -> Info {
-        Info {
-            name: "CORS support",
-            kind: Kind::Response,
+    /// When enabled, preflights with `Access-Control-Request-Private-Network:
+    /// true`, sent by Chrome before public sites reach private addresses, get
+    /// `Access-Control-Allow-Private-Network: true`. Disabled by default.
+    pub fn allow_private_network(mut self, allow: bool) -> Self {
+        self.allow_private_network = allow;
+        self
+    }
+
+    /// Like `allow_private_network`, for the `Access-Control-Request-Local-Network`
+    /// and `Access-Control-Allow-Local-Network` headers the local network
+    /// access draft renames them to. Enable both while browsers move over.
+    pub fn allow_local_network(mut self, allow: bool) -> Self {
+        self.allow_local_network = allow;
+        self
+    }
+
+    /// Lists `headers` in the `Access-Control-Expose-Headers` header of actual
+    /// responses, letting scripts read them. Endpoints can replace this list
+    /// with `expose_headers_for`.
+    /// `"*"` exposes every header; with `allow_credentials`, for which
+    /// browsers take it literally, it is replaced by the headers of each
+    /// response.
+    pub fn expose_headers(mut self, headers: &[&str]) -> Self {
+        self.expose_headers = headers.iter().map(|h| (*h).to_owned()).collect();
+        self
+    }
+
+    /// Exposes `headers` instead of the fairing-wide list on the endpoints
+    /// declared with `path`, like only exposing `Content-Disposition`
+    /// on a download endpoint. An empty list exposes no header there.
+    pub fn expose_headers_for<P: IntoEndpointPath>(self, path: P, headers: &[&str]) -> Self {
+        self.policy_for(path, EndpointPolicy::new().expose_headers(headers))
+    }
+
+    /// Applies the settings of `policy` instead of those of the fairing on the
+    /// endpoints declared with `path`, like only allowing one origin on admin
+    /// endpoints. Paths are compared as templates, so that "/admin/<page>" is
+    /// "/admin/:page". Settings left unset in `policy` are kept, so that
+    /// policies for the same path add up. See the `policy` module.
+    ///
+    /// `validate` refuses paths no endpoint is declared with, for which the
+    /// policy never applies.
+    pub fn policy_for<P: IntoEndpointPath>(mut self, path: P, policy: EndpointPolicy) -> Self {
+        let path = path.into_endpoint_path();
+        let template = PathTemplate::parse(&path).ok();
+        let mut matched = false;
+        for (endpoint, endpoint_policy) in self.templates
+            .iter()
+            .zip(self.endpoint_policies.iter_mut())
+        {
+            if same_template(endpoint, &template) {
+                endpoint_policy.merge(&policy);
+                matched = true;
+            }
+        }
+        if !matched {
+            self.unmatched_policies.push(path);
         }
+        self
     }
 
-    fn on_response(&self, request: &Request, mut response: &mut Response) {
-        if self.is_allowed(request) {
-            CORS::add_headers(&mut response);
-            if request.method() == Method::Options {
-                // Just return an empty response for CORS Options.
-                response.set_status(Status::Ok);
-                response.set_raw_body(Body::Sized(Cursor::new(""), 0));
+    /// Like `policy_for`, but only for `methods` on the endpoints declared
+    /// with `path`, like only allowing the admin origin to `DELETE` where any
+    /// origin can `GET`. Endpoints also allowing other methods are split in
+    /// two, the one with `methods` right after, so that the first endpoint
+    /// allowing a method still decides for it.
+    ///
+    /// Preflights are then matched with the method they ask for, as with
+    /// `reject_disallowed_methods`, so that they get the policy of that
+    /// method.
+    pub fn policy_for_methods<P: IntoEndpointPath>(
+        mut self,
+        path: P,
+        methods: &[Method],
+        policy: EndpointPolicy,
+    ) -> Self {
+        let path = path.into_endpoint_path();
+        let template = PathTemplate::parse(&path).ok();
+        let mut matched = false;
+        let mut index = 0;
+        while index < self.allowed_endpoints.len() {
+            if !same_template(&self.templates[index], &template) {
+                index += 1;
+                continue;
+            }
+            let (scoped, others): (Vec<Method>, Vec<Method>) = self.allowed_endpoints[index]
+                .0
+                .iter()
+                .cloned()
+                .partition(|method| methods.contains(method));
+            if scoped.is_empty() {
+                index += 1;
+                continue;
+            }
+            if !others.is_empty() {
+                let endpoint_path = self.allowed_endpoints[index].1.clone();
+                let endpoint_template = self.templates[index].clone();
+                let endpoint_policy = self.endpoint_policies[index].clone();
+                self.allowed_endpoints[index].0 = others;
+                index += 1;
+                self.allowed_endpoints.insert(index, (scoped, endpoint_path));
+                self.templates.insert(index, endpoint_template);
+                self.endpoint_policies.insert(index, endpoint_policy);
             }
+            self.endpoint_policies[index].merge(&policy);
+            matched = true;
+            index += 1;
         }
+        if !matched {
+            self.unmatched_policies.push(path);
+        }
+        self.method_policies = true;
+        self
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::CORS;
-    use rocket::{self, Response};
-    use rocket::http::{Method, Status};
-    use rocket::local::Client;
+    /// Lets browsers cache the answers to preflights for `max_age`, sending it
+    /// in whole seconds as `Access-Control-Max-Age`. Browsers cap it, at 2
+    /// hours for Chromium. Without it, which is the default, they preflight
+    /// again after 5 seconds.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
 
-    #[get("/endpoint")]
-    fn endpoint() -> &'static str {
-        "Hello World!"
+    /// When enabled, a preflight asking for a method that no endpoint allows on
+    /// an otherwise configured path gets a `405 Method Not Allowed` response
+    /// with an `Allow` header listing the permitted methods.
+    /// Disabled by default: such preflights get no CORS headers.
+    pub fn reject_disallowed_methods(mut self, reject: bool) -> Self {
+        self.reject_disallowed_methods = reject;
+        self
     }
 
-    fn verify_no_cors_reponse(response: &mut Response) {
-        assert_eq!(response.status(), Status::Ok);
+    /// When enabled, refused preflights get an RFC 7807
+    /// `application/problem+json` body describing why, instead of an empty
+    /// one. Disabled by default.
+    pub fn problem_details(mut self, enabled: bool) -> Self {
+        self.problem_details = enabled;
+        self
+    }
 
-        let body_str = response.body().and_then(|b| b.into_string());
-        assert_eq!(body_str, Some("Hello World!".to_string()));
+    /// Sets the status of granted preflights, `200 OK` by default. They have
+    /// an empty body, or none at all with `204 No Content`, which some
+    /// proxies and clients expect.
+    pub fn preflight_status(mut self, status: Status) -> Self {
+        self.preflight_status = status;
+        self
+    }
 
-        let values: Vec<_> = response
-            .headers()
-            .get("Access-Control-Allow-Origin")
-            .collect();
-        assert_eq!(values.len(), 0);
+    /// Sets how preflights that aren't granted are answered, to fail closed
+    /// with `PreflightFailureMode::Forbidden`. `reject_disallowed_methods`
+    /// takes precedence for the preflights it refuses.
+    pub fn preflight_failure_mode(mut self, mode: PreflightFailureMode) -> Self {
+        self.preflight_failure_mode = mode;
+        self
     }
 
-    fn verify_cors_response_with(response: &mut Response, body: &str) {
-        assert_eq!(response.status(), Status::Ok);
+    /// Sets the methods listed in `Access-Control-Allow-Methods`, instead of
+    /// the methods allowed by the endpoints matching the request path.
+    pub fn allowed_methods<M: Into<MethodSet>>(mut self, methods: M) -> Self {
+        self.advertised_methods = Some(methods.into());
+        self
+    }
 
-        let body_str = response.body().and_then(|b| b.into_string());
-        assert_eq!(body_str, Some(body.to_string()));
+    /// When enabled, `OPTIONS` is listed in `Access-Control-Allow-Methods`.
+    /// The spec doesn't require it, but some tools expect it.
+    /// Disabled by default.
+    pub fn advertise_options(mut self, advertise: bool) -> Self {
+        self.advertise_options = advertise;
+        self
+    }
 
-        let values: Vec<_> = response
-            .headers()
-            .get("Access-Control-Allow-Origin")
-            .collect();
-        assert_eq!(values.len(), 1);
-        assert_eq!(values[0], "*");
+    /// When enabled, preflights are granted only if an endpoint allows the
+    /// method named in `Access-Control-Request-Method`, and
+    /// `Access-Control-Allow-Methods` lists only that method instead of the
+    /// whole set, disclosing less about the API.
+    /// Disabled by default.
+    pub fn reflect_requested_method(mut self, reflect: bool) -> Self {
+        self.reflect_requested_method = reflect;
+        self
+    }
 
-        let values: Vec<_> = response
-            .headers()
-            .get("Access-Control-Allow-Headers")
-            .collect();
-        assert_eq!(values.len(), 1);
-        assert_eq!(
-            values[0],
-            "accept, accept-language, authorization, content-type"
-        );
+    /// When enabled, endpoints allowing `GET` also allow `HEAD`, and `HEAD` is
+    /// advertised next to `GET` in `Access-Control-Allow-Methods`.
+    /// Enabled by default.
+    pub fn head_with_get(mut self, enabled: bool) -> Self {
+        self.head_with_get = enabled;
+        self
+    }
 
-        let values: Vec<_> = response
-            .headers()
-            .get("Access-Control-Allow-Methods")
-            .collect();
-        assert_eq!(values.len(), 1);
-        assert_eq!(values[0], "GET, POST, PUT, DELETE");
+    /// Grants requests the use of `headers` instead of the default ones.
+    pub fn allowed_headers(mut self, headers: AllowedHeaders) -> Self {
+        self.allowed_headers = headers;
+        self
     }
 
-    fn verify_cors_response(response: &mut Response) {
-        verify_cors_response_with(response, "Hello World!")
+    /// Only grants CORS access to requests from `origins`, instead of any
+    /// origin.
+    pub fn allowed_origins(mut self, origins: AllowedOrigins) -> Self {
+        self.allowed_origins = origins;
+        self
     }
 
-    #[test]
-    fn no_cors() {
-        let rocket = rocket::ignite().mount("/", routes![endpoint]);
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/endpoint").dispatch();
-        verify_no_cors_reponse(&mut response);
+    /// Always denies requests from `origins`, whatever allows them otherwise.
+    /// Entries can be patterns like "https://*.example.com", as in
+    /// `AllowedOrigins::Some`.
+    pub fn blocked_origins(mut self, origins: &[&str]) -> Self {
+        self.blocked_origins = OriginList::new(origins);
+        self
     }
 
-    #[test]
-    fn cors_simple() {
-        let rocket = rocket::ignite()
-            .mount("/", routes![endpoint])
-            .attach(cors!("/endpoint" => Method::Get, Method::Put));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/endpoint").dispatch();
-        verify_cors_response(&mut response);
+    /// Also allows `entry`, an origin or a pattern as in `AllowedOrigins::Some`,
+    /// until `until`, like for a short-lived partner integration. Endpoints
+    /// replacing the allowed origins with `policy_for` aren't affected, and
+    /// blocked origins stay blocked. Expired entries are ignored, and listed
+    /// by `expired_origins` and `validation_report` so they can be removed.
+    pub fn temporary_origin(mut self, entry: &str, until: Instant) -> Self {
+        self.temporary_origins.push((OriginList::new(&[entry]), until));
+        self
     }
 
-    #[test]
-    fn cors_simple_no_slash() {
-        let rocket = rocket::ignite()
-            .mount("/", routes![endpoint])
-            .attach(cors!("/endpoint" => Method::Get));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/endpoint").dispatch();
-        verify_cors_response(&mut response);
+    /// The entries given to `temporary_origin` that have expired.
+    pub fn expired_origins(&self) -> Vec<&str> {
+        let now = self.clock.now();
+        self.temporary_origins
+            .iter()
+            .filter(|&&(_, until)| until <= now)
+            .flat_map(|&(ref origins, _)| origins.entries())
+            .map(|entry| entry.as_str())
+            .collect()
     }
 
-    #[test]
-    fn cors_bad_method() {
-        let rocket = rocket::ignite()
-            .mount("/", routes![endpoint])
-            .attach(cors!("/endpoint" => Method::Put));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/endpoint").dispatch();
-        verify_no_cors_reponse(&mut response);
+    /// Uses `clock` instead of the system clock to expire temporary origins.
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
     }
 
-    #[test]
-    fn cors_wrong_path_len() {
-        let rocket = rocket::ignite()
-            .mount("/", routes![endpoint])
-            .attach(cors!("/some/endpoint" => Method::Get));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/endpoint").dispatch();
-        verify_no_cors_reponse(&mut response);
+    /// When enabled, requests with `Origin: null`, sent by sandboxed iframes
+    /// and after some redirects, are allowed whatever the allowed origins.
+    /// Disabled by default: they are denied, even when any origin is allowed,
+    /// since any document can be made to send it.
+    pub fn allow_null_origin(mut self, allow: bool) -> Self {
+        self.allow_null_origin = allow;
+        self
     }
 
-    #[test]
-    fn cors_wrong_path_segments() {
-        let rocket = rocket::ignite()
-            .mount("/another", routes![endpoint])
-            .attach(cors!("/some/endpoint" => Method::Get));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/another/endpoint").dispatch();
-        verify_no_cors_reponse(&mut response);
+    /// When enabled, which is the default, requests without a valid `Origin`
+    /// header, like same-origin ones and those of curl, get no CORS headers.
+    /// Disable it to answer them as requests from any origin.
+    pub fn require_origin(mut self, require: bool) -> Self {
+        self.require_origin = require;
+        self
     }
 
-    #[test]
-    fn cors_test_preflight() {
-        let rocket = rocket::ignite()
-            .mount("/", routes![endpoint])
-            .attach(cors!("/endpoint" => Method::Get));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.options("/endpoint").dispatch();
-        verify_cors_response_with(&mut response, "");
+    /// Mounts `preflight_routes(rank)` when the fairing is attached, so that
+    /// preflights are answered as soon as they are routed. With a rank below
+    /// those of the application's `OPTIONS` routes, their handlers only get
+    /// the `OPTIONS` requests that aren't preflights.
+    pub fn mount_preflight_routes(mut self, rank: isize) -> Self {
+        self.preflight_rank = Some(rank);
+        self
     }
 
-    #[test]
-    fn cors_variable_path() {
-        let rocket = rocket::ignite()
-            .mount("/cors", routes![endpoint])
-            .attach(cors!("/cors/:something" => Method::Get));
-        let client = Client::new(rocket).expect("valid rocket instance");
-        let mut response = client.get("/cors/endpoint").dispatch();
+    /// When enabled, requests with neither an `Origin` header nor any of the
+    /// `Sec-Fetch-*` ones browsers send, like those between services, are
+    /// left alone at once: they aren't evaluated, counted, recorded nor seen
+    /// by response hooks. Disabled by default.
+    pub fn skip_non_browser(mut self, skip: bool) -> Self {
+        self.skip_non_browser = skip;
+        self
+    }
+
+    /// When enabled, allowed requests get `Access-Control-Allow-Credentials:
+    /// true`, letting them carry cookies and `Authorization` headers. Since
+    /// browsers reject credentials granted to `*`, this also makes allowed
+    /// requests get their own origin, like `reflect_origin`, and credentialed
+    /// preflights get the allowed headers they ask for rather than the whole
+    /// list: no header of a credentialed response carries a wildcard.
+    /// Disabled by default.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// When enabled, allowed requests get their own origin in
+    /// `Access-Control-Allow-Origin` even when any origin is allowed, instead
+    /// of `*`. Reflected origins always come with `Vary: Origin`.
+    /// Disabled by default.
+    pub fn reflect_origin(mut self, reflect: bool) -> Self {
+        self.reflect_origin = reflect;
+        self
+    }
+
+    /// Sets what happens to requests on paths no endpoint matches, instead of
+    /// denying them. Combine with `record_into` to log them.
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Sets the casing of the names of all the headers this fairing sets,
+    /// for clients and proxies sensitive to it.
+    pub fn header_case(mut self, case: HeaderCase) -> Self {
+        self.header_case = case;
+        self
+    }
+
+    /// When enabled, every response, including denied ones, gets a `Vary`
+    /// header listing the headers of `cache_key`, so that caches in front of
+    /// the application never serve the answer to one origin to another.
+    /// Disabled by default.
+    pub fn vary_on_cache_key(mut self, enabled: bool) -> Self {
+        self.vary_on_cache_key = enabled;
+        self
+    }
+
+    /// Adds a hook run on the headers computed for each response, in the
+    /// order hooks were added.
+    pub fn response_hook<H: ResponseHook>(mut self, hook: H) -> Self {
+        self.response_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Also consults `validator` to decide whether the origin of a request
+    /// gets CORS access. Allowed requests then get their own origin in
+    /// `Access-Control-Allow-Origin`.
+    /// Only the fairing runs the validator, since it needs the `Request`.
+    pub fn origin_validator<V: OriginValidator>(mut self, validator: V) -> Self {
+        self.origin_validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Records the time spent on each response into `timings`.
+    pub fn time_into(mut self, timings: timing::Timings) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Counts the requests of each origin into `rates`, for origin validators
+    /// and response hooks holding a clone of it. See the `rates` module.
+    pub fn count_origins(mut self, rates: rates::OriginRates) -> Self {
+        self.origin_rates = Some(rates);
+        self
+    }
+
+    /// Records every decision taken by this fairing into `log`.
+    #[cfg(feature = "recording")]
+    pub fn record_into(mut self, log: recording::DecisionLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Copies the value of the `name` request header, like "X-Request-Id",
+    /// into the recorded decisions so they can be tied to client traces.
+    /// Give it to `monitor::DenialMonitor::correlation_header` too for its
+    /// reports.
+    #[cfg(feature = "recording")]
+    pub fn correlation_header(mut self, name: &str) -> Self {
+        self.correlation_header = Some(name.to_owned());
+        self
+    }
+
+    /// Evaluates a request using `method` on `path`.
+    /// The first endpoint matching the request wins.
+    pub fn evaluate(&self, method: Method, path: &str) -> Decision {
+        let mut path_matched = false;
+        for (index, (&(ref methods, _), template)) in
+            self.allowed_endpoints
+                .iter()
+                .zip(self.templates.iter())
+                .enumerate()
+        {
+            match *template {
+                Some(ref template) if template.matches(path) => {
+                    if self.endpoint_allows(methods, method) {
+                        let enabled = self.endpoint_policies[index]
+                            .switch
+                            .as_ref()
+                            .map_or(true, |switch| switch.is_enabled());
+                        if !enabled {
+                            return Decision::EndpointDisabled(index);
+                        }
+                        return Decision::Allowed(index);
+                    }
+                    path_matched = true;
+                }
+                _ => {}
+            }
+        }
+        if path_matched {
+            return Decision::MethodNotAllowed;
+        }
+        match self.fallback {
+            Fallback::Deny => Decision::NoMatchingPath,
+            Fallback::Allow(ref methods) if self.endpoint_allows(methods.as_slice(), method) => {
+                Decision::Fallback
+            }
+            Fallback::Allow(_) => Decision::MethodNotAllowed,
+        }
+    }
+
+    /// The methods allowed by the endpoints matching `path`, in the order
+    /// they are configured, or by the fallback policy if none matches.
+    pub fn methods_for(&self, path: &str) -> Vec<Method> {
+        let mut allowed: Vec<Method> = vec![];
+        for (&(ref methods, _), template) in
+            self.allowed_endpoints.iter().zip(self.templates.iter())
+        {
+            if !template.as_ref().map_or(false, |t| t.matches(path)) {
+                continue;
+            }
+            for method in self.with_implied_methods(methods) {
+                if !allowed.contains(&method) {
+                    allowed.push(method);
+                }
+            }
+        }
+        if allowed.is_empty() {
+            if let Fallback::Allow(ref methods) = self.fallback {
+                return self.with_implied_methods(methods.as_slice());
+            }
+        }
+        allowed
+    }
 
-        verify_cors_response(&mut response);
+    /// Returns true if an endpoint declared with `methods` allows `method`.
+    fn endpoint_allows(&self, methods: &[Method], method: Method) -> bool {
+        matching::method_matches(methods, method) ||
+            (self.head_with_get && method == Method::Head && methods.contains(&Method::Get))
+    }
+
+    /// Returns `methods` with the ones they imply added: `HEAD` right after
+    /// `GET` unless disabled with `head_with_get`.
+    pub(crate) fn with_implied_methods(&self, methods: &[Method]) -> Vec<Method> {
+        let mut all = Vec::with_capacity(methods.len() + 1);
+        for method in methods {
+            if all.contains(method) {
+                continue;
+            }
+            all.push(*method);
+            if *method == Method::Get && self.head_with_get && !methods.contains(&Method::Head) {
+                all.push(Method::Head);
+            }
+        }
+        all
+    }
+
+    /// Returns true if a request using `method` on `path` gets CORS headers.
+    pub fn allows(&self, method: Method, path: &str) -> bool {
+        self.evaluate(method, path).is_allowed()
+    }
+
+    /// Evaluates a request described by `meta`, taking the method requested by
+    /// preflights into account when configured to.
+    /// Unlike `evaluate`, the origin of the request is checked too.
+    pub fn evaluate_meta(&self, meta: &RequestMeta) -> Decision {
+        if meta.method == Method::Options && meta.malformed_request_method {
+            return Decision::MalformedPreflight;
+        }
+        let mut method = meta.method;
+        if self.reject_disallowed_methods || self.reflect_requested_method ||
+            self.method_policies
+        {
+            if let Some(requested) = meta.preflight_method() {
+                method = requested;
+            }
+        }
+        let decision = self.evaluate(method, meta.path);
+        let endpoint = match decision {
+            Decision::Allowed(endpoint) => Some(endpoint),
+            _ => None,
+        };
+        // Parsed once for every entry, borrowing from the request.
+        let parsed = meta.origin.and_then(|origin| OriginRef::parse(origin).ok());
+        let origin_allowed = match meta.origin {
+            Some("null") => self.allow_null_origin,
+            origin => {
+                (self.allowed_origins_of(endpoint).allows_parsed(origin, parsed.as_ref()) ||
+                    self.temporarily_allows(endpoint, parsed.as_ref())) &&
+                    !self.blocks(parsed.as_ref())
+            }
+        };
+        let valid_origin = meta.origin == Some("null") || parsed.is_some();
+        if decision.is_allowed() && self.require_origin && !valid_origin {
+            return Decision::MissingOrigin;
+        }
+        if decision.is_allowed() && !origin_allowed {
+            return Decision::OriginNotAllowed;
+        }
+        decision
+    }
+
+    /// Checks that browsers accept this configuration and that it doesn't
+    /// hand credentials to any origin, returning the first mistake found.
+    /// See the `validation` module.
+    pub fn validate(self) -> Result<Self, validation::ConfigError> {
+        match validation::errors(&self).into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(self),
+        }
+    }
+
+    /// Lists the mistakes `validate` refuses along with warnings about likely
+    /// ones, for CI to fail on. See the `validation` module.
+    pub fn validation_report(&self) -> validation::ValidationReport {
+        validation::report(self)
+    }
+
+    /// The changes from this policy to `other`, to log when replacing it.
+    /// See the `diff` module.
+    pub fn diff(&self, other: &CORS) -> diff::PolicyDiff {
+        diff::diff(self, other)
+    }
+
+    /// Looks for endpoints that can never match. See the `lint` module.
+    pub fn lint(&self) -> Vec<lint::Lint> {
+        lint::lint(self)
+    }
+
+    /// `OPTIONS` routes for the paths of the endpoints, to mount so that
+    /// preflights are routed rather than caught as 404s. Ranks go from `rank`
+    /// away from zero, one per distinct path; see the `preflight` module.
+    pub fn preflight_routes(&self, rank: isize) -> Vec<Route> {
+        preflight::routes(self, rank)
+    }
+
+    /// Evaluates each expectation and reports the ones this policy doesn't
+    /// meet. See the `selftest` module.
+    pub fn self_test(&self, expectations: &[Expectation]) -> SelfTestReport {
+        selftest::run(self, expectations)
+    }
+
+    /// Runs `self_test(expectations)` when the fairing is attached, failing
+    /// the launch and printing the report to stderr if any isn't met.
+    pub fn self_test_on_attach(mut self, expectations: &[Expectation]) -> Self {
+        self.attach_expectations.extend(expectations.iter().map(
+            |&(method, path, origin, ref decision)| {
+                (method, path.to_owned(), origin.map(|o| o.to_owned()), decision.clone())
+            },
+        ));
+        self
+    }
+
+    /// Returns true if the expectations of `self_test_on_attach` are met.
+    fn passes_attach_self_test(&self) -> bool {
+        let expectations: Vec<Expectation> = self.attach_expectations
+            .iter()
+            .map(|&(method, ref path, ref origin, ref decision)| {
+                (method, path.as_str(), origin.as_ref().map(|o| o.as_str()), decision.clone())
+            })
+            .collect();
+        let report = self.self_test(&expectations);
+        if !report.is_ok() {
+            eprintln!("CORS policy self-test failed: {}", report);
+        }
+        report.is_ok()
+    }
+
+    /// Returns true if the fairing has something to do when it is attached.
+    fn attaches(&self) -> bool {
+        self.preflight_rank.is_some() || !self.attach_expectations.is_empty()
+    }
+
+    /// Returns true if `origin` is allowed on `endpoint` by a temporary origin
+    /// that hasn't expired.
+    fn temporarily_allows(&self, endpoint: Option<usize>, origin: Option<&OriginRef>) -> bool {
+        let replaced = endpoint.map_or(false, |endpoint| {
+            self.endpoint_policies[endpoint].allowed_origins.is_some()
+        });
+        match origin {
+            Some(origin) if !replaced && !self.temporary_origins.is_empty() => {
+                let now = self.clock.now();
+                self.temporary_origins
+                    .iter()
+                    .any(|&(ref origins, until)| now < until && origins.matches(origin))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if `origin` is blocked with `blocked_origins`.
+    fn blocks(&self, origin: Option<&OriginRef>) -> bool {
+        origin.map_or(false, |origin| self.blocked_origins.matches(origin))
+    }
+
+    /// The request headers the answers of this fairing depend on, which
+    /// caches like Varnish or a CDN must add to their cache key.
+    pub fn cache_key(&self) -> Vec<&'static str> {
+        let mut headers = vec![];
+        let policies = &self.endpoint_policies;
+        let credentials = self.allow_credentials ||
+            policies.iter().any(|p| p.allow_credentials == Some(true));
+        let origin_dependent = self.allowed_origins != AllowedOrigins::All ||
+            policies.iter().any(|p| p.allowed_origins.is_some()) ||
+            !self.blocked_origins.is_empty() || !self.allow_null_origin || self.require_origin ||
+            self.reflect_origin || credentials || self.origin_validator.is_some();
+        if origin_dependent {
+            headers.push("Origin");
+        }
+        if self.reject_disallowed_methods || self.reflect_requested_method {
+            headers.push("Access-Control-Request-Method");
+        }
+        let echoes_headers = credentials || self.allowed_headers == AllowedHeaders::Echo ||
+            policies
+                .iter()
+                .any(|p| p.allowed_headers == Some(AllowedHeaders::Echo));
+        if echoes_headers {
+            headers.push("Access-Control-Request-Headers");
+        }
+        headers
+    }
+
+    /// The access granted to the request described by `meta`, or `None` if it
+    /// isn't granted CORS access.
+    pub fn grant_for(&self, meta: &RequestMeta) -> Option<Grant> {
+        match self.evaluate_meta(meta) {
+            Decision::Allowed(endpoint) => Some(self.grant(meta, Some(endpoint))),
+            Decision::Fallback => Some(self.grant(meta, None)),
+            _ => None,
+        }
+    }
+
+    /// The headers this fairing sets on the response to the request described
+    /// by `meta`, or `None` if it isn't granted CORS access.
+    /// Response hooks aren't run, since they need the actual `Request`.
+    pub fn headers_for(&self, meta: &RequestMeta) -> Option<Vec<Header<'static>>> {
+        self.grant_for(meta).map(Grant::into)
+    }
+
+    /// Turns `response` into a refusal of a preflight, that must not be cached
+    /// so that fixing the policy takes effect immediately. Its body is empty,
+    /// or describes `decision` if `problem_details` is enabled.
+    fn refuse_preflight(&self, response: &mut Response, status: Status, decision: &Decision) {
+        response.set_status(status);
+        self.set_header(response, Header::new("Cache-Control", "no-store"));
+        response.remove_header("Access-Control-Max-Age");
+        if !self.problem_details {
+            CORS::clear_body(response);
+            return;
+        }
+        response.remove_header("Content-Length");
+        self.set_header(response, ContentType::new("application", "problem+json").into());
+        response.set_sized_body(Cursor::new(problem_body(status, decision)));
+    }
+
+    /// Sets `header` on `response`, with its name in the configured case.
+    fn set_header(&self, response: &mut Response, header: Header<'static>) {
+        match self.header_case {
+            HeaderCase::Standard => response.set_header(header),
+            HeaderCase::Lower => {
+                // Rocket keeps the name of a replaced header, so remove it first.
+                let name = header.name().to_ascii_lowercase();
+                response.remove_header(&name);
+                response.set_header(Header::new(name, header.value().to_owned()))
+            }
+        };
+    }
+
+    /// Adds the values of the `vary` header to those `response` already has,
+    /// so that the ones set by the application are kept.
+    fn merge_vary(&self, response: &mut Response, vary: &Header) {
+        let mut values: Vec<String> = response
+            .headers()
+            .get("Vary")
+            .flat_map(|value| value.split(','))
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+            .collect();
+        // The response already varies on everything.
+        if values.iter().any(|value| value == "*") {
+            return;
+        }
+        for value in vary.value().split(',').map(|value| value.trim()) {
+            if !value.is_empty() && !values.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+                values.push(value.to_owned());
+            }
+        }
+        if !values.is_empty() {
+            self.set_header(response, Header::new("Vary", values.join(", ")));
+        }
+    }
+
+    /// Empties the body of `response`, if it has one. A `Content-Length` set by
+    /// the handler is dropped too, so that it can't disagree with the body.
+    fn clear_body(response: &mut Response) {
+        response.remove_header("Content-Length");
+        if response.body().is_some() {
+            response.set_raw_body(Body::Sized(Cursor::new(""), 0));
+        }
+    }
+
+    /// The methods listed in `Access-Control-Allow-Methods` on the response
+    /// to `meta`.
+    pub(crate) fn advertised_methods(&self, meta: &RequestMeta) -> Vec<Method> {
+        let mut methods = match (meta.preflight_method(), &self.advertised_methods) {
+            (Some(method), _) if self.reflect_requested_method => vec![method],
+            (_, &Some(ref methods)) => self.with_implied_methods(methods.as_slice()),
+            (_, &None) => self.methods_for(meta.path),
+        };
+        if self.advertise_options && !methods.contains(&Method::Options) {
+            methods.push(Method::Options);
+        }
+        methods
+    }
+
+    /// The access granted to the request described by `meta`, matching
+    /// `endpoint` if any.
+    pub(crate) fn grant(&self, meta: &RequestMeta, endpoint: Option<usize>) -> Grant {
+        let policy = endpoint.map(|endpoint| &self.endpoint_policies[endpoint]);
+        let allow_credentials = policy
+            .and_then(|policy| policy.allow_credentials)
+            .unwrap_or(self.allow_credentials);
+        let reflect = match *self.allowed_origins_of(endpoint) {
+            AllowedOrigins::All => {
+                self.reflect_origin || allow_credentials || self.origin_validator.is_some()
+            }
+            _ => true,
+        };
+        let allow_origin = match meta.origin {
+            Some(origin) if reflect => origin.to_owned(),
+            _ => "*".to_owned(),
+        };
+        // Browsers reject credentials granted to any origin.
+        let mode = if allow_credentials && allow_origin != "*" {
+            Mode::Credentialed
+        } else {
+            Mode::Anonymous
+        };
+        // Caches must not serve an answer to a request depending on one of
+        // its headers to requests with another value.
+        let mut vary = vec![];
+        if allow_origin != "*" {
+            vary.push("Origin".to_owned());
+        }
+        if meta.preflight_method().is_some() {
+            if self.reflect_requested_method {
+                vary.push("Access-Control-Request-Method".to_owned());
+            }
+            if mode == Mode::Credentialed ||
+                *self.allowed_headers_of(endpoint) == AllowedHeaders::Echo
+            {
+                vary.push("Access-Control-Request-Headers".to_owned());
+            }
+        }
+        let mut expose_headers = self.exposed_headers(meta, endpoint);
+        // Browsers take `*` literally on credentialed responses, so `respond`
+        // lists the headers of the response instead.
+        if mode == Mode::Credentialed {
+            expose_headers.retain(|header| header != "*");
+        }
+        Grant {
+            allow_origin: allow_origin,
+            allow_credentials: mode == Mode::Credentialed,
+            // Only the answers to preflights are cached, not those to plain
+            // OPTIONS requests.
+            max_age: match meta.preflight_method() {
+                Some(_) => self.max_age_of(endpoint),
+                None => None,
+            },
+            vary: vary,
+            allow_headers: self.granted_headers(meta, endpoint, mode),
+            allow_methods: self.advertised_methods(meta),
+            expose_headers: expose_headers,
+            extra_headers: self.granted_extra_headers(meta),
+        }
+    }
+
+    /// The max age of the preflights on `endpoint`, in seconds.
+    pub(crate) fn max_age_of(&self, endpoint: Option<usize>) -> Option<u64> {
+        endpoint
+            .and_then(|endpoint| self.endpoint_policies[endpoint].max_age)
+            .or(self.max_age)
+            .map(|max_age| max_age.as_secs())
+    }
+
+    /// The extra headers, along with the network access granted to preflights.
+    fn granted_extra_headers(&self, meta: &RequestMeta) -> Vec<(String, String)> {
+        let mut headers = self.extra_headers.clone();
+        if meta.preflight_method().is_none() {
+            return headers;
+        }
+        if self.allow_private_network && meta.request_private_network {
+            headers.push(("Access-Control-Allow-Private-Network".to_owned(), "true".to_owned()));
+        }
+        if self.allow_local_network && meta.request_local_network {
+            headers.push(("Access-Control-Allow-Local-Network".to_owned(), "true".to_owned()));
+        }
+        headers
+    }
+
+    /// The request headers granted to `meta`. Credentialed preflights get the
+    /// allowed ones among the headers they ask for, exactly as asked.
+    fn granted_headers(
+        &self,
+        meta: &RequestMeta,
+        endpoint: Option<usize>,
+        mode: Mode,
+    ) -> Vec<String> {
+        let requested = match meta.request_headers {
+            Some(requested) if meta.method == Method::Options => requested.trim(),
+            _ => "",
+        };
+        let allowed_headers = self.allowed_headers_of(endpoint);
+        match *allowed_headers {
+            AllowedHeaders::Some(ref allowed)
+                if mode == Mode::Anonymous || requested.is_empty() =>
+            {
+                allowed.clone()
+            }
+            AllowedHeaders::Some(_) => requested
+                .split(',')
+                .map(|header| header.trim())
+                .filter(|header| allowed_headers.allows(header))
+                .map(|header| header.to_owned())
+                .collect(),
+            AllowedHeaders::All if mode == Mode::Anonymous => vec!["*".to_owned()],
+            AllowedHeaders::All | AllowedHeaders::Echo if requested.is_empty() => vec![],
+            AllowedHeaders::All | AllowedHeaders::Echo => vec![requested.to_owned()],
+        }
+    }
+
+    /// The headers exposed on the response to `meta`: none on preflights,
+    /// which scripts never read.
+    fn exposed_headers(&self, meta: &RequestMeta, endpoint: Option<usize>) -> Vec<String> {
+        if meta.method == Method::Options {
+            return vec![];
+        }
+        endpoint
+            .and_then(|endpoint| self.endpoint_policies[endpoint].expose_headers.clone())
+            .unwrap_or_else(|| self.expose_headers.clone())
+    }
+
+    /// The request headers allowed on `endpoint`, or by the fairing.
+    fn allowed_headers_of(&self, endpoint: Option<usize>) -> &AllowedHeaders {
+        endpoint
+            .and_then(|endpoint| self.endpoint_policies[endpoint].allowed_headers.as_ref())
+            .unwrap_or(&self.allowed_headers)
+    }
+
+    /// The origins allowed on `endpoint`, or by the fairing.
+    fn allowed_origins_of(&self, endpoint: Option<usize>) -> &AllowedOrigins {
+        endpoint
+            .and_then(|endpoint| self.endpoint_policies[endpoint].allowed_origins.as_ref())
+            .unwrap_or(&self.allowed_origins)
+    }
+}
+
+/// Returns true if both templates are valid and match the same paths.
+fn same_template(a: &Option<PathTemplate>, b: &Option<PathTemplate>) -> bool {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) => a.covers(b) && b.covers(a),
+        _ => false,
+    }
+}
+
+/// Returns true if `request` may come from a browser: it has an `Origin`
+/// header or a `Sec-Fetch-*` one.
+fn from_browser(request: &Request) -> bool {
+    let headers = request.headers();
+    headers.contains("Origin") ||
+        headers.iter().any(|header| {
+            header
+                .name()
+                .get(..10)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("sec-fetch-"))
+        })
+}
+
+/// An RFC 7807 problem document describing the refusal of a preflight with
+/// `status`, because of `decision`.
+fn problem_body(status: Status, decision: &Decision) -> String {
+    format!(
+        "{{\"type\":\"about:blank\",\"title\":\"CORS preflight refused\",\
+         \"status\":{},\"reason\":\"{}\"}}",
+        status.code,
+        decision.reason()
+    )
+}
+
+impl From<Vec<CORSEndpoint>> for CORS {
+    fn from(endpoints: Vec<CORSEndpoint>) -> Self {
+        CORS::new(endpoints)
+    }
+}
+
+impl From<CORSEndpoint> for CORS {
+    fn from(endpoint: CORSEndpoint) -> Self {
+        CORS::new(vec![endpoint])
+    }
+}
+
+impl<'a> From<&'a [(&'a [Method], &'a str)]> for CORS {
+    fn from(endpoints: &'a [(&'a [Method], &'a str)]) -> Self {
+        CORS::from_slice(endpoints)
+    }
+}
+
+impl<'a> From<(&'a [Method], &'a str)> for CORS {
+    fn from(endpoint: (&'a [Method], &'a str)) -> Self {
+        CORS::from_slice(&[endpoint])
+    }
+}
+
+impl Fairing for CORS {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS support",
+            kind: if self.attaches() {
+                Kind::Attach | Kind::Response
+            } else {
+                Kind::Response
+            },
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        if !self.passes_attach_self_test() {
+            return Err(rocket);
+        }
+        match self.preflight_rank {
+            Some(rank) => Ok(rocket.mount("/", self.preflight_routes(rank))),
+            None => Ok(rocket),
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        match self.timings {
+            Some(ref timings) => {
+                let start = Instant::now();
+                self.respond(request, response);
+                timings.record(start.elapsed());
+            }
+            None => self.respond(request, response),
+        }
+    }
+}
+
+impl CORS {
+    /// Adds the CORS headers to `response`, and answers preflights.
+    fn respond(&self, request: &Request, response: &mut Response) {
+        // Left for other CORS fairings, and removed by `exempt::Exemptions`.
+        if response.headers().contains(exempt::EXEMPT_HEADER) {
+            return;
+        }
+        if self.skip_non_browser && !from_browser(request) {
+            return;
+        }
+        let meta = RequestMeta::from(request);
+        if let (&Some(ref rates), Some(origin)) = (&self.origin_rates, meta.origin) {
+            rates.record(origin);
+        }
+        let mut decision = self.evaluate_meta(&meta);
+        if decision.is_allowed() {
+            if let Some(ref validator) = self.origin_validator {
+                if !meta.origin.map_or(false, |origin| validator.allows(origin, request)) {
+                    decision = Decision::OriginNotAllowed;
+                }
+            }
+        }
+        #[cfg(feature = "recording")]
+        {
+            if let Some(ref log) = self.log {
+                let correlation_header = self.correlation_header.as_ref().map(|h| h.as_str());
+                log.push(recording::Record::new(request, &decision, correlation_header));
+            }
+        }
+        let endpoint = match decision {
+            Decision::Allowed(endpoint) => Some(endpoint),
+            _ => None,
+        };
+        let grant = match decision {
+            Decision::Allowed(_) | Decision::Fallback => Some(self.grant(&meta, endpoint)),
+            _ => None,
+        };
+        let mut headers = match grant {
+            Some(mut grant) => {
+                // Credentialed responses can't expose `*`: list the headers
+                // of this one instead.
+                let exposes_all = self.exposed_headers(&meta, endpoint).iter().any(|h| h == "*");
+                if grant.allow_credentials && exposes_all {
+                    grant.expose_headers = response
+                        .headers()
+                        .iter()
+                        .map(|header| header.name().to_string())
+                        .collect();
+                }
+                grant.into()
+            }
+            // Requests from other origins could be granted access.
+            None if decision == Decision::OriginNotAllowed ||
+                decision == Decision::MissingOrigin =>
+            {
+                vec![Header::new("Vary", "Origin")]
+            }
+            None => vec![],
+        };
+        if self.vary_on_cache_key {
+            let cache_key = self.cache_key();
+            headers.retain(|header| !header.name().eq_ignore_ascii_case("Vary"));
+            if !cache_key.is_empty() {
+                headers.push(Header::new("Vary", cache_key.join(", ")));
+            }
+        }
+        for hook in &self.response_hooks {
+            hook.on_headers(request, &decision, &mut headers);
+        }
+        for header in headers {
+            if header.name().eq_ignore_ascii_case("Vary") {
+                self.merge_vary(response, &header);
+            } else {
+                self.set_header(response, header);
+            }
+        }
+        // Bodies are left alone on other requests, including plain OPTIONS
+        // ones and HEAD ones whose body Rocket strips itself.
+        if !meta.is_preflight() {
+            return;
+        }
+        if decision.is_allowed() {
+            // Just return an empty response for CORS Options.
+            response.set_status(self.preflight_status);
+            if self.preflight_status == Status::NoContent {
+                response.remove_header("Content-Length");
+                response.take_body();
+            } else {
+                CORS::clear_body(response);
+            }
+        } else if decision == Decision::MethodNotAllowed && self.reject_disallowed_methods {
+            let methods = self.methods_for(request.uri().path());
+            let methods: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+            self.set_header(response, Header::new("Allow", methods.join(", ")));
+            self.refuse_preflight(response, Status::MethodNotAllowed, &decision);
+        } else if self.preflight_failure_mode == PreflightFailureMode::Forbidden {
+            self.refuse_preflight(response, Status::Forbidden, &decision);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AllowedHeaders, AllowedOrigins, Decision, Fallback, HeaderCase, MethodSet,
+                PreflightFailureMode, RequestMeta, CORS};
+    use clock::MockClock;
+    use policy::EndpointPolicy;
+    use rocket::{self, Request, Response};
+    use rocket::http::{Header, Method, Status};
+    use rocket::local::Client;
+    use std::io::Cursor;
+    use std::time::Duration;
+    use validation::Warning;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[put("/endpoint")]
+    fn put_endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[delete("/api/<user>")]
+    fn delete_user(user: String) -> String {
+        user
+    }
+
+    fn origin() -> Header<'static> {
+        Header::new("Origin", "https://example.com")
+    }
+
+    fn verify_no_cors_reponse(response: &mut Response) {
+        assert_eq!(response.status(), Status::Ok);
+
+        let body_str = response.body().and_then(|b| b.into_string());
+        assert_eq!(body_str, Some("Hello World!".to_string()));
+
+        let values: Vec<_> = response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .collect();
+        assert_eq!(values.len(), 0);
+    }
+
+    fn verify_cors_response_with(response: &mut Response, body: &str, methods: &str) {
+        assert_eq!(response.status(), Status::Ok);
+
+        let body_str = response.body().and_then(|b| b.into_string());
+        assert_eq!(body_str, Some(body.to_string()));
+
+        let values: Vec<_> = response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .collect();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0], "*");
+
+        let values: Vec<_> = response
+            .headers()
+            .get("Access-Control-Allow-Headers")
+            .collect();
+        assert_eq!(values.len(), 1);
+        assert_eq!(
+            values[0],
+            "accept, accept-language, authorization, content-type"
+        );
+
+        let values: Vec<_> = response
+            .headers()
+            .get("Access-Control-Allow-Methods")
+            .collect();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0], methods);
+    }
+
+    fn verify_cors_response(response: &mut Response, methods: &str) {
+        verify_cors_response_with(response, "Hello World!", methods)
+    }
+
+    #[test]
+    fn no_cors() {
+        let rocket = rocket::ignite().mount("/", routes![endpoint]);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_no_cors_reponse(&mut response);
+    }
+
+    #[test]
+    fn cors_simple() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get, Method::Put));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_cors_response(&mut response, "GET, HEAD, PUT");
+    }
+
+    #[test]
+    fn cors_simple_no_slash() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_cors_response(&mut response, "GET, HEAD");
+    }
+
+    #[test]
+    fn cors_bad_method() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Put));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_no_cors_reponse(&mut response);
+    }
+
+    #[test]
+    fn cors_wrong_path_len() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/some/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_no_cors_reponse(&mut response);
+    }
+
+    #[test]
+    fn cors_wrong_path_segments() {
+        let rocket = rocket::ignite()
+            .mount("/another", routes![endpoint])
+            .attach(cors!("/some/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/another/endpoint").header(origin()).dispatch();
+        verify_no_cors_reponse(&mut response);
+    }
+
+    #[test]
+    fn cors_test_preflight() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        verify_cors_response_with(&mut response, "", "GET, HEAD");
+    }
+
+    #[test]
+    fn cors_variable_path() {
+        let rocket = rocket::ignite()
+            .mount("/cors", routes![endpoint])
+            .attach(cors!("/cors/:something" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/cors/endpoint").header(origin()).dispatch();
+
+        verify_cors_response(&mut response, "GET, HEAD");
+    }
+
+    #[test]
+    fn cors_invalid_path() {
+        let endpoints = vec![(vec![Method::Get], "/foo//bar".to_owned())];
+        assert!(CORS::try_new(endpoints.clone()).is_err());
+        assert!(!CORS::new(endpoints).allows(Method::Get, "/foo/bar"));
+    }
+
+    #[test]
+    fn cors_extra_headers() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).extra_header("X-Frame-Options", "DENY"));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_cors_response(&mut response, "GET, HEAD");
+        assert_eq!(response.headers().get_one("X-Frame-Options"), Some("DENY"));
+
+        let response = client.put("/endpoint").header(origin()).dispatch();
+        assert_eq!(response.headers().get_one("X-Frame-Options"), None);
+    }
+
+    #[test]
+    fn cors_preflight_method_not_allowed() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(
+                cors!("/endpoint" => Method::Get; "/endpoint" => Method::Put)
+                    .reject_disallowed_methods(true),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "DELETE"))
+            .dispatch();
+        assert_eq!(response.status(), Status::MethodNotAllowed);
+        assert_eq!(response.headers().get_one("Allow"), Some("GET, HEAD, PUT"));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("no-store"));
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+
+        let mut response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "PUT"))
+            .dispatch();
+        verify_cors_response_with(&mut response, "", "GET, HEAD, PUT");
+    }
+
+    #[test]
+    fn cors_from_slice() {
+        let cors = CORS::from_slice(&[
+            (&[Method::Get], "/endpoint"),
+            (&[Method::Put, Method::Delete], "/api/:user"),
+        ]);
+        assert!(cors.allows(Method::Delete, "/api/john"));
+        assert!(!cors.allows(Method::Get, "/api/john"));
+
+        let methods: &[Method] = &[Method::Get];
+        let cors = CORS::from((methods, "/endpoint"));
+        assert!(cors.allows(Method::Get, "/endpoint"));
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(CORS::from((vec![Method::Get], "/endpoint".to_owned())));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").header(origin()).dispatch();
+        verify_cors_response(&mut response, "GET, HEAD");
+    }
+
+    #[test]
+    fn cors_from_routes() {
+        let cors = CORS::from_routes(routes![endpoint, put_endpoint, delete_user]).unwrap();
+        assert_eq!(
+            cors.endpoints(),
+            &[
+                (vec![Method::Get, Method::Put], "/endpoint".to_owned()),
+                (vec![Method::Delete], "/api/<user>".to_owned()),
+            ]
+        );
+        assert!(cors.allows(Method::Delete, "/api/john"));
+    }
+
+    #[test]
+    fn cors_advertise_options() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).advertise_options(true));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client.options("/endpoint").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Methods"),
+            Some("GET, HEAD, OPTIONS")
+        );
+    }
+
+    #[test]
+    fn cors_preflight_status() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).preflight_status(Status::NoContent));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+        assert!(response.body().is_none());
+    }
+
+    #[test]
+    fn cors_reflect_requested_method() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(
+                cors!("/endpoint" => Method::Get, Method::Put)
+                    .reflect_requested_method(true)
+                    .advertise_options(true),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "PUT"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Methods"),
+            Some("PUT, OPTIONS")
+        );
+
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "DELETE"))
+            .dispatch();
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn cors_head_with_get() {
+        let cors = cors!("/endpoint" => Method::Get);
+        assert!(cors.allows(Method::Head, "/endpoint"));
+        let cors = cors.head_with_get(false);
+        assert!(!cors.allows(Method::Head, "/endpoint"));
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors.allowed_methods(&[Method::Get, Method::Post][..]));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client.options("/endpoint").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Methods"),
+            Some("GET, POST")
+        );
+    }
+
+    #[test]
+    fn cors_allowed_methods() {
+        let methods = MethodSet::defaults().with(Method::Options);
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).allowed_methods(methods));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client.get("/endpoint").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Methods"),
+            Some("GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS")
+        );
+    }
+
+    #[test]
+    fn cors_headers_for() {
+        let cors = cors!("/endpoint" => Method::Get).reflect_requested_method(true);
+
+        let meta = RequestMeta {
+            origin: Some("https://example.com"),
+            ..RequestMeta::new(Method::Get, "/endpoint")
+        };
+        let headers = cors.headers_for(&meta).expect("CORS headers");
+        assert_eq!(headers[0].name(), "Access-Control-Allow-Origin");
+        assert_eq!(headers[0].value(), "*");
+
+        let preflight = RequestMeta::preflight("/endpoint", "https://example.com", Method::Get);
+        let headers = cors.headers_for(&preflight).expect("CORS headers");
+        assert!(headers.contains(&Header::new("Access-Control-Allow-Methods", "GET")));
+
+        assert_eq!(cors.headers_for(&RequestMeta::new(Method::Put, "/endpoint")), None);
+    }
+
+    #[test]
+    fn cors_scoped() {
+        let cors = cors!("/users/:id" => Method::Get; "/" => Method::Get).scoped("/api/");
+        assert_eq!(
+            cors.endpoints(),
+            &[
+                (vec![Method::Get], "/api/users/:id".to_owned()),
+                (vec![Method::Get], "/api".to_owned()),
+            ]
+        );
+        assert!(cors.allows(Method::Get, "/api/users/42"));
+        assert!(cors.allows(Method::Get, "/api"));
+        assert!(!cors.allows(Method::Get, "/users/42"));
+    }
+
+    #[options("/status_only")]
+    fn status_only() -> Status {
+        Status::NoContent
+    }
+
+    #[test]
+    fn cors_head_request() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let mut response = client.head("/endpoint").header(origin()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("*")
+        );
+        assert!(response.body_string().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn cors_status_only_preflight() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![status_only])
+            .attach(cors!("/status_only" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let mut response = client
+            .options("/status_only")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Length"), None);
+        assert_eq!(response.body_string(), None);
+
+        // Plain OPTIONS requests are left to the application.
+        let response = client
+            .options("/status_only")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let response = client
+            .options("/status_only")
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+    }
+
+    #[get("/download")]
+    fn download() -> &'static str {
+        "data"
+    }
+
+    #[test]
+    fn cors_expose_headers() {
+        let cors = cors!("/endpoint" => Method::Get; "/download" => Method::Get)
+            .expose_headers(&["ETag", "X-Total-Count"])
+            .expose_headers_for("/download", &["Content-Disposition"]);
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint, download])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client.get("/endpoint").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Expose-Headers"),
+            Some("ETag, X-Total-Count")
+        );
+        let response = client.get("/download").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Expose-Headers"),
+            Some("Content-Disposition")
+        );
+        let response = client.options("/download").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Expose-Headers"),
+            None
+        );
+    }
+
+    #[test]
+    fn cors_fallback() {
+        let cors = cors!("/endpoint" => Method::Put);
+        assert_eq!(cors.evaluate(Method::Get, "/other"), Decision::NoMatchingPath);
+
+        let cors = cors.fallback(Fallback::Allow(MethodSet::empty().with(Method::Get)));
+        assert_eq!(cors.evaluate(Method::Get, "/other"), Decision::Fallback);
+        assert_eq!(cors.evaluate(Method::Delete, "/other"), Decision::MethodNotAllowed);
+        assert_eq!(cors.evaluate(Method::Get, "/endpoint"), Decision::MethodNotAllowed);
+        assert_eq!(cors.methods_for("/other"), vec![Method::Get, Method::Head]);
+        assert_eq!(cors.methods_for("/endpoint"), vec![Method::Put]);
+    }
+
+    #[test]
+    fn cors_allowed_origins() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]));
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://app.example.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://app.example.com")
+        );
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://evil.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+
+        let response = client.get("/endpoint").dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+    }
+
+    #[test]
+    fn cors_problem_details() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(
+                cors!("/endpoint" => Method::Get)
+                    .reject_disallowed_methods(true)
+                    .problem_details(true),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let mut response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "DELETE"))
+            .dispatch();
+        assert_eq!(response.status(), Status::MethodNotAllowed);
+        assert_eq!(
+            response.headers().get_one("Content-Type"),
+            Some("application/problem+json")
+        );
+        assert_eq!(
+            response.body_string(),
+            Some(
+                "{\"type\":\"about:blank\",\"title\":\"CORS preflight refused\",\
+                 \"status\":405,\"reason\":\"method-not-allowed\"}"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn cors_preflight_failure_mode() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(
+                cors!("/endpoint" => Method::Get)
+                    .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+                    .preflight_failure_mode(PreflightFailureMode::Forbidden),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let preflight = |origin: &str| {
+            client
+                .options("/endpoint")
+                .header(Header::new("Origin", origin.to_owned()))
+                .header(Header::new("Access-Control-Request-Method", "GET"))
+                .dispatch()
+        };
+
+        let mut response = preflight("https://evil.com");
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("no-store"));
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+        assert_eq!(response.body_string(), Some(String::new()));
+
+        assert_eq!(preflight("https://example.com").status(), Status::Ok);
+        // Other requests are left alone.
+        let mut response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://evil.com"))
+            .dispatch();
+        verify_no_cors_reponse(&mut response);
+    }
+
+    #[test]
+    fn cors_network_access() {
+        let cors = cors!("/endpoint" => Method::Get).allow_local_network(true);
+        let network_headers = |private: bool, local: bool| {
+            let meta = RequestMeta {
+                request_private_network: private,
+                request_local_network: local,
+                ..RequestMeta::preflight("/endpoint", "https://example.com", Method::Get)
+            };
+            cors.headers_for(&meta)
+                .expect("CORS headers")
+                .into_iter()
+                .filter(|header| header.name().ends_with("-Network"))
+                .map(|header| header.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            network_headers(true, true),
+            vec!["Access-Control-Allow-Local-Network"]
+        );
+        assert!(network_headers(true, false).is_empty());
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).allow_private_network(true));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .header(Header::new("Access-Control-Request-Private-Network", "true"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Private-Network"),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn cors_require_origin() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = client.get("/endpoint").dispatch();
+        verify_no_cors_reponse(&mut response);
+        let mut response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "not an origin"))
+            .dispatch();
+        verify_no_cors_reponse(&mut response);
+
+        let cors = cors!("/endpoint" => Method::Get);
+        let meta = RequestMeta::new(Method::Get, "/endpoint");
+        assert_eq!(cors.evaluate_meta(&meta), Decision::MissingOrigin);
+        let cors = cors.require_origin(false);
+        assert_eq!(cors.evaluate_meta(&meta), Decision::Allowed(0));
+    }
+
+    #[get("/negotiated")]
+    fn negotiated() -> Response<'static> {
+        Response::build()
+            .raw_header("Vary", "Accept-Encoding")
+            .sized_body(Cursor::new("Hello World!"))
+            .finalize()
+    }
+
+    #[test]
+    fn cors_vary() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![negotiated])
+            .attach(
+                cors!("/negotiated" => Method::Get)
+                    .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+                    .allow_credentials(true),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client.get("/negotiated").header(origin()).dispatch();
+        assert_eq!(
+            response.headers().get_one("Vary"),
+            Some("Accept-Encoding, Origin")
+        );
+        let response = client
+            .options("/negotiated")
+            .header(origin())
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Vary"),
+            Some("Origin, Access-Control-Request-Headers")
+        );
+        let response = client
+            .get("/negotiated")
+            .header(Header::new("Origin", "https://evil.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Vary"),
+            Some("Accept-Encoding, Origin")
+        );
+    }
+
+    #[test]
+    fn cors_malformed_preflight() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(
+                cors!("/endpoint" => Method::Get)
+                    .preflight_failure_mode(PreflightFailureMode::Forbidden),
+            );
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let preflight = |methods: &[&str]| {
+            let mut request = client.options("/endpoint").header(origin());
+            for method in methods {
+                request = request.header(Header::new(
+                    "Access-Control-Request-Method",
+                    (*method).to_owned(),
+                ));
+            }
+            request.dispatch()
+        };
+
+        let response = preflight(&["GET", "PUT"]);
+        assert_eq!(response.status(), Status::Forbidden);
+        assert!(!response.headers().contains("Access-Control-Allow-Origin"));
+        assert_eq!(preflight(&["NOT A METHOD"]).status(), Status::Forbidden);
+        assert_eq!(preflight(&["GET"]).status(), Status::Ok);
+
+        let meta = RequestMeta {
+            request_method: None,
+            malformed_request_method: true,
+            ..RequestMeta::preflight("/endpoint", "https://example.com", Method::Get)
+        };
+        let cors = cors!("/endpoint" => Method::Get);
+        assert_eq!(cors.evaluate_meta(&meta), Decision::MalformedPreflight);
+    }
+
+    #[test]
+    fn cors_skip_non_browser() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .skip_non_browser(true)
+            .response_hook(
+                |_: &Request, _: &Decision, headers: &mut Vec<Header<'static>>| {
+                    headers.push(Header::new("X-Evaluated", "true"));
+                },
+            );
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let evaluated = |header: Option<Header<'static>>| {
+            let mut request = client.get("/endpoint");
+            if let Some(header) = header {
+                request = request.header(header);
+            }
+            request.dispatch().headers().contains("X-Evaluated")
+        };
+
+        assert!(!evaluated(None));
+        assert!(evaluated(Some(Header::new("Sec-Fetch-Mode", "navigate"))));
+        assert!(evaluated(Some(origin())));
+    }
+
+    #[test]
+    fn cors_header_case() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).header_case(HeaderCase::Lower));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client.get("/endpoint").header(origin()).dispatch();
+        let mut headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .filter(|header| header.name().to_ascii_lowercase().starts_with("access-control-"))
+            .map(|header| (header.name().to_owned(), header.value().to_owned()))
+            .collect();
+        headers.sort();
+        let expected = [
+            (
+                "access-control-allow-headers",
+                "accept, accept-language, authorization, content-type",
+            ),
+            ("access-control-allow-methods", "GET, HEAD"),
+            ("access-control-allow-origin", "*"),
+        ];
+        assert_eq!(
+            headers,
+            expected
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn cors_reflect_origin() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).reflect_origin(true));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+
+        // Requests without an origin get no CORS headers.
+        let response = client.get("/endpoint").dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn cors_blocked_origins() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://*.partner.com"]))
+            .blocked_origins(&["https://compromised.partner.com"]);
+        let evaluate = |origin: &str| {
+            cors.evaluate_meta(&RequestMeta {
+                origin: Some(origin),
+                ..RequestMeta::new(Method::Get, "/endpoint")
+            })
+        };
+        assert_eq!(evaluate("https://app.partner.com"), Decision::Allowed(0));
+        assert_eq!(evaluate("https://compromised.partner.com"), Decision::OriginNotAllowed);
+
+        let cors = cors!("/endpoint" => Method::Get).blocked_origins(&["https://evil.com"]);
+        assert!(cors.headers_for(&RequestMeta {
+            origin: Some("https://evil.com"),
+            ..RequestMeta::new(Method::Get, "/endpoint")
+        }).is_none());
+    }
+
+    #[test]
+    fn cors_null_origin() {
+        let meta = RequestMeta {
+            origin: Some("null"),
+            ..RequestMeta::new(Method::Get, "/endpoint")
+        };
+        let cors = cors!("/endpoint" => Method::Get);
+        assert_eq!(cors.evaluate_meta(&meta), Decision::OriginNotAllowed);
+
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+            .allow_null_origin(true);
+        assert_eq!(cors.evaluate_meta(&meta), Decision::Allowed(0));
+        let headers = cors.headers_for(&meta).expect("CORS headers");
+        assert_eq!(headers[0].value(), "null");
+    }
+
+    #[test]
+    fn cors_allow_credentials() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).allow_credentials(true));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+
+        let response = client.get("/endpoint").dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Credentials"),
+            None
+        );
+    }
+
+    #[test]
+    fn cors_credentialed_mode() {
+        let preflight = |client: &Client| {
+            let response = client
+                .options("/endpoint")
+                .header(Header::new("Origin", "https://example.com"))
+                .header(Header::new("Access-Control-Request-Method", "GET"))
+                .header(Header::new("Access-Control-Request-Headers", "Content-Type, X-Secret"))
+                .dispatch();
+            response
+                .headers()
+                .get_one("Access-Control-Allow-Headers")
+                .map(|h| h.to_owned())
+        };
+        let exposed = |client: &Client| {
+            let response = client
+                .get("/endpoint")
+                .header(Header::new("Origin", "https://example.com"))
+                .dispatch();
+            response
+                .headers()
+                .get_one("Access-Control-Expose-Headers")
+                .map(|h| h.to_owned())
+        };
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).expose_headers(&["*"]));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        assert_eq!(
+            preflight(&client).as_ref().map(|h| h.as_str()),
+            Some("accept, accept-language, authorization, content-type")
+        );
+        assert_eq!(exposed(&client).as_ref().map(|h| h.as_str()), Some("*"));
+
+        let rocket = rocket::ignite().mount("/", routes![endpoint]).attach(
+            cors!("/endpoint" => Method::Get)
+                .expose_headers(&["*"])
+                .allow_credentials(true),
+        );
+        let client = Client::new(rocket).expect("valid rocket instance");
+        assert_eq!(
+            preflight(&client).as_ref().map(|h| h.as_str()),
+            Some("Content-Type")
+        );
+        let exposed = exposed(&client).unwrap();
+        assert!(exposed.contains("Content-Type"));
+        assert!(!exposed.contains('*'));
+    }
+
+    #[test]
+    fn cors_allowed_headers() {
+        let allowed_headers = |headers: AllowedHeaders, credentials: bool| {
+            let cors = cors!("/endpoint" => Method::Get)
+                .allowed_headers(headers)
+                .allow_credentials(credentials);
+            let meta = RequestMeta {
+                request_headers: Some("X-Requested-With, X-Custom"),
+                ..RequestMeta::preflight("/endpoint", "https://example.com", Method::Get)
+            };
+            cors.headers_for(&meta)
+                .expect("CORS headers")
+                .into_iter()
+                .find(|header| header.name() == "Access-Control-Allow-Headers")
+                .map(|header| header.value().to_owned())
+        };
+        let echo = Some("X-Requested-With, X-Custom".to_owned());
+
+        assert_eq!(allowed_headers(AllowedHeaders::All, false), Some("*".to_owned()));
+        assert_eq!(allowed_headers(AllowedHeaders::All, true), echo);
+        assert_eq!(allowed_headers(AllowedHeaders::Echo, false), echo);
+        assert_eq!(
+            allowed_headers(AllowedHeaders::some(&["x-requested-with"]), true),
+            Some("X-Requested-With".to_owned())
+        );
+    }
+
+    #[test]
+    fn cors_max_age() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).max_age(Duration::from_secs(600)));
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Max-Age"),
+            Some("600")
+        );
+
+        let response = client.get("/endpoint").header(origin()).dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Max-Age"), None);
+    }
+
+    #[test]
+    fn cors_cache_key() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .allow_null_origin(true)
+            .require_origin(false);
+        assert!(cors.cache_key().is_empty());
+        let cors = cors.reflect_requested_method(true).allow_credentials(true);
+        assert_eq!(
+            cors.cache_key(),
+            vec![
+                "Origin",
+                "Access-Control-Request-Method",
+                "Access-Control-Request-Headers",
+            ]
+        );
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).vary_on_cache_key(true));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "null"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn cors_endpoint_policies() {
+        let cors = cors!("/public/:file" => Method::Get; "/admin/:page" => Method::Get)
+            .max_age(Duration::from_secs(60))
+            .policy_for(
+                "/admin/:page",
+                EndpointPolicy::new()
+                    .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]))
+                    .allow_credentials(true),
+            )
+            .policy_for(
+                "/admin/:page",
+                EndpointPolicy::new().max_age(Duration::from_secs(5)),
+            );
+
+        let public = RequestMeta::preflight("/public/logo.png", "https://any.com", Method::Get);
+        let grant = cors.grant_for(&public).expect("public access");
+        assert_eq!(grant.allow_origin, "*");
+        assert!(!grant.allow_credentials);
+        assert_eq!(grant.max_age, Some(60));
+
+        let admin = RequestMeta::preflight("/admin/users", "https://any.com", Method::Get);
+        assert_eq!(cors.evaluate_meta(&admin), Decision::OriginNotAllowed);
+        let origin = "https://admin.example.com";
+        let admin = RequestMeta::preflight("/admin/users", origin, Method::Get);
+        let grant = cors.grant_for(&admin).expect("admin access");
+        assert_eq!(grant.allow_origin, origin);
+        assert!(grant.allow_credentials);
+        assert_eq!(grant.max_age, Some(5));
+    }
+
+    #[test]
+    fn cors_method_policies() {
+        let admin = "https://admin.example.com";
+        let cors = cors!("/items/:id" => Method::Get, Method::Delete).policy_for_methods(
+            "/items/:id",
+            &[Method::Delete],
+            EndpointPolicy::new().allowed_origins(AllowedOrigins::some(&[admin])),
+        );
+        assert_eq!(
+            cors.endpoints(),
+            &[
+                (vec![Method::Get], "/items/:id".to_owned()),
+                (vec![Method::Delete], "/items/:id".to_owned()),
+            ]
+        );
+
+        let origin = "https://any.com";
+        let get = RequestMeta {
+            origin: Some(origin),
+            ..RequestMeta::new(Method::Get, "/items/1")
+        };
+        assert_eq!(cors.evaluate_meta(&get), Decision::Allowed(0));
+        let delete = RequestMeta {
+            method: Method::Delete,
+            ..get
+        };
+        assert_eq!(cors.evaluate_meta(&delete), Decision::OriginNotAllowed);
+        let preflight = RequestMeta::preflight("/items/1", origin, Method::Delete);
+        assert_eq!(cors.evaluate_meta(&preflight), Decision::OriginNotAllowed);
+        let preflight = RequestMeta::preflight("/items/1", admin, Method::Delete);
+        assert_eq!(cors.evaluate_meta(&preflight), Decision::Allowed(1));
+        let preflight = RequestMeta::preflight("/items/1", origin, Method::Get);
+        assert_eq!(cors.evaluate_meta(&preflight), Decision::Allowed(0));
+        assert!(cors.validate().is_ok());
+    }
+
+    #[test]
+    fn cors_temporary_origins() {
+        let clock = MockClock::new();
+        let partner = "https://partner.example.com";
+        let cors = cors!("/items" => Method::Get; "/admin" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]))
+            .policy_for(
+                "/admin",
+                EndpointPolicy::new()
+                    .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"])),
+            )
+            .with_clock(clock.clone())
+            .temporary_origin(partner, clock.now() + Duration::from_secs(60));
+        let request = |path: &'static str| RequestMeta {
+            origin: Some(partner),
+            ..RequestMeta::new(Method::Get, path)
+        };
+
+        assert_eq!(cors.evaluate_meta(&request("/items")), Decision::Allowed(0));
+        assert_eq!(cors.evaluate_meta(&request("/admin")), Decision::OriginNotAllowed);
+        assert!(cors.expired_origins().is_empty());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(cors.evaluate_meta(&request("/items")), Decision::OriginNotAllowed);
+        assert_eq!(cors.expired_origins(), vec![partner]);
+        assert!(
+            cors.validation_report()
+                .warnings
+                .contains(&Warning::ExpiredOrigin(partner.to_owned()))
+        );
     }
 }
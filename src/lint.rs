@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Detection of configuration mistakes that don't prevent building a fairing,
+//! but make part of its policy dead.
+
+use CORS;
+use rocket::http::Method;
+use std::fmt;
+use template::{PathTemplate, TemplateError};
+
+/// A configuration mistake found by `CORS::lint`.
+/// Endpoints are identified by their index in `CORS::endpoints()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// The path of the endpoint isn't a valid template, so it never matches.
+    InvalidPath(usize, TemplateError),
+    /// The endpoint never matches: for each of its methods, an earlier
+    /// endpoint matching at least all of its paths wins.
+    ShadowedEndpoint(usize),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Lint::InvalidPath(index, ref error) => {
+                write!(f, "endpoint {} never matches: {}", index, error)
+            }
+            Lint::ShadowedEndpoint(index) => {
+                write!(f, "endpoint {} never matches: earlier endpoints shadow it", index)
+            }
+        }
+    }
+}
+
+pub(crate) fn lint(cors: &CORS) -> Vec<Lint> {
+    let mut lints = vec![];
+    for (index, &(ref methods, ref path)) in cors.allowed_endpoints.iter().enumerate() {
+        let template = match cors.templates[index] {
+            Some(ref template) => template,
+            None => {
+                if let Err(error) = PathTemplate::parse(path) {
+                    lints.push(Lint::InvalidPath(index, error));
+                }
+                continue;
+            }
+        };
+        // The earlier endpoints matching at least all the paths of this one.
+        let covering: Vec<&[Method]> = cors.allowed_endpoints[..index]
+            .iter()
+            .zip(cors.templates.iter())
+            .filter(|&(_, earlier)| earlier.as_ref().map_or(false, |t| t.covers(template)))
+            .map(|(&(ref methods, _), _)| methods.as_slice())
+            .collect();
+        // OPTIONS always goes to the first matching endpoint.
+        let shadowed = !covering.is_empty() &&
+            cors.with_implied_methods(methods)
+                .into_iter()
+                .filter(|method| *method != Method::Options)
+                .all(|method| {
+                    covering
+                        .iter()
+                        .any(|earlier| cors.endpoint_allows(earlier, method))
+                });
+        if shadowed {
+            lints.push(Lint::ShadowedEndpoint(index));
+        }
+    }
+    lints
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lint;
+    use CORS;
+    use rocket::http::Method;
+    use template::TemplateError;
+
+    #[test]
+    fn shadowed_endpoints() {
+        let cors = cors!(
+            "/api/:user" => Method::Get, Method::Put;
+            "/api/admin" => Method::Get;
+            "/api/admin" => Method::Delete;
+            "/api/:user" => Method::Head;
+            "/api//" => Method::Get
+        );
+        assert_eq!(
+            cors.lint(),
+            vec![
+                Lint::ShadowedEndpoint(1),
+                Lint::ShadowedEndpoint(3),
+                Lint::InvalidPath(4, TemplateError::EmptySegment(5)),
+            ]
+        );
+        assert!(cors!("/endpoint" => Method::Get).lint().is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The matching logic used by the fairing, as pure functions over plain
+//! values so it can be exercised without building a `Request`.
+
+use rocket::http::Method;
+use template::PathTemplate;
+
+/// Returns true if `path` (eg. "/api/john/action") matches the endpoint
+/// path `template` (eg. "/api/:user/action").
+/// Empty segments of `path` are ignored, like Rocket does for request URIs,
+/// and invalid templates never match.
+pub fn path_matches(template: &str, path: &str) -> bool {
+    PathTemplate::parse(template)
+        .map(|template| template.matches(path))
+        .unwrap_or(false)
+}
+
+/// Turns a path template into a concrete path it matches, replacing each
+/// variable part by its name: "/api/:user/action" and "/api/<user>/action"
+/// become "/api/user/action".
+pub fn literalize(template: &str) -> String {
+    let template = if template.starts_with('/') { &template[1..] } else { template };
+    template
+        .split('/')
+        .map(|segment| if segment.starts_with(':') {
+            &segment[1..]
+        } else if segment.len() >= 2 && segment.starts_with('<') && segment.ends_with('>') {
+            &segment[1..segment.len() - 1]
+        } else {
+            segment
+        })
+        .fold(String::new(), |path, segment| path + "/" + segment)
+}
+
+/// Returns true if a request using `method` is covered by an endpoint
+/// allowing `methods`.
+/// `OPTIONS` requests are always covered, since they are used for preflights.
+pub fn method_matches(methods: &[Method], method: Method) -> bool {
+    method == Method::Options || methods.contains(&method)
+}
+
+/// Returns true if a request using `method` on `path` is covered by an
+/// endpoint allowing `methods` on `template`.
+pub fn endpoint_matches(methods: &[Method], template: &str, method: Method, path: &str) -> bool {
+    method_matches(methods, method) && path_matches(template, path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{endpoint_matches, literalize, path_matches};
+    use rocket::http::Method;
+
+    #[test]
+    fn literal_paths() {
+        assert!(path_matches("/endpoint", "/endpoint"));
+        assert!(path_matches("endpoint", "/endpoint"));
+        assert!(path_matches("/some/endpoint", "/some//endpoint/"));
+        assert!(!path_matches("/some/endpoint", "/endpoint"));
+        assert!(!path_matches("/some/endpoint", "/another/endpoint"));
+        assert!(!path_matches("/some/endpoint/", "/some/endpoint"));
+    }
+
+    #[test]
+    fn variable_paths() {
+        assert!(path_matches("/api/:user/action", "/api/john/action"));
+        assert!(!path_matches("/api/:user/action", "/api/john"));
+        assert!(!path_matches("/api/:user/action", "/api/john/delete"));
+    }
+
+    #[test]
+    fn literalized_paths() {
+        assert_eq!(literalize("/api/:user/action"), "/api/user/action");
+        assert_eq!(literalize("/api/<user>/action"), "/api/user/action");
+        assert_eq!(literalize("endpoint"), "/endpoint");
+        for template in &["/endpoint", "/api/:user/action", "/:a/<b>"] {
+            assert!(path_matches(template, &literalize(template)));
+        }
+    }
+
+    #[test]
+    fn methods() {
+        let methods = [Method::Get, Method::Put];
+        assert!(endpoint_matches(&methods, "/endpoint", Method::Put, "/endpoint"));
+        assert!(endpoint_matches(&methods, "/endpoint", Method::Options, "/endpoint"));
+        assert!(!endpoint_matches(&methods, "/endpoint", Method::Delete, "/endpoint"));
+    }
+}
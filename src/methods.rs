@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Sets of methods advertised in `Access-Control-Allow-Methods`.
+
+use DEFAULT_METHODS;
+use rocket::http::Method;
+
+/// An ordered set of methods.
+///
+/// Organizations can define their own baseline once and share it:
+///
+/// ```
+/// # extern crate rocket;
+/// # extern crate rocket_cors;
+/// # fn main() {
+/// use rocket::http::Method;
+/// use rocket_cors::MethodSet;
+///
+/// fn baseline() -> MethodSet {
+///     MethodSet::defaults().without(Method::Delete).with(Method::Options)
+/// }
+/// # assert!(baseline().contains(Method::Options));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodSet {
+    methods: Vec<Method>,
+}
+
+impl MethodSet {
+    /// An empty set.
+    pub fn empty() -> Self {
+        MethodSet { methods: vec![] }
+    }
+
+    /// The crate's default set, `DEFAULT_METHODS`:
+    /// GET, HEAD, POST, PUT, PATCH and DELETE.
+    pub fn defaults() -> Self {
+        MethodSet::from(DEFAULT_METHODS)
+    }
+
+    /// Adds `method` at the end of the set, if it isn't there yet.
+    pub fn with(mut self, method: Method) -> Self {
+        if !self.methods.contains(&method) {
+            self.methods.push(method);
+        }
+        self
+    }
+
+    /// Removes `method` from the set.
+    pub fn without(mut self, method: Method) -> Self {
+        self.methods.retain(|m| *m != method);
+        self
+    }
+
+    /// Returns true if the set contains `method`.
+    pub fn contains(&self, method: Method) -> bool {
+        self.methods.contains(&method)
+    }
+
+    /// The methods of the set, in order.
+    pub fn as_slice(&self) -> &[Method] {
+        &self.methods
+    }
+}
+
+impl Default for MethodSet {
+    fn default() -> Self {
+        MethodSet::defaults()
+    }
+}
+
+impl<'a> From<&'a [Method]> for MethodSet {
+    fn from(methods: &'a [Method]) -> Self {
+        methods
+            .iter()
+            .fold(MethodSet::empty(), |set, method| set.with(*method))
+    }
+}
+
+impl From<Vec<Method>> for MethodSet {
+    fn from(methods: Vec<Method>) -> Self {
+        MethodSet::from(methods.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MethodSet;
+    use rocket::http::Method;
+
+    #[test]
+    fn method_sets() {
+        let set = MethodSet::defaults();
+        assert_eq!(set, MethodSet::default());
+        assert!(set.contains(Method::Patch));
+
+        let set = set.without(Method::Patch).with(Method::Options).with(Method::Get);
+        assert_eq!(
+            set.as_slice(),
+            &[
+                Method::Get,
+                Method::Head,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Options,
+            ]
+        );
+        assert_eq!(MethodSet::from(vec![Method::Get, Method::Get]).as_slice(), &[Method::Get]);
+    }
+}
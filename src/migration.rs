@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Two policies attached together, so that a new one can be rolled out with
+//! a new version of an API while clients of the old version keep the old
+//! one.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::migration::DualStack;
+//!
+//! let v1 = cors!("/api/:user" => Method::Get);
+//! let v2 = cors!("/api/:user" => Method::Get, Method::Put);
+//! let rocket = rocket::ignite().attach(DualStack::header(v1, v2, "X-Api-Version", "2"));
+//! # }
+//! ```
+
+use CORS;
+use preflight;
+use rocket::{Request, Response, Rocket};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+
+/// A fairing applying either the `current` policy or the `next` one, chosen
+/// for each request by a cutover rule.
+///
+/// Responses vary on the headers the cutover rule reads, as given to
+/// `DualStack::varying_on` or by `DualStack::header`, so that caches don't
+/// serve the answer of one policy to the clients of the other.
+///
+/// When both policies mount their preflight routes, the routes are mounted
+/// once for the paths of both, with the rank given to `current`.
+pub struct DualStack {
+    current: CORS,
+    next: CORS,
+    cutover: Box<Fn(&Request) -> bool + Send + Sync>,
+    vary: Vec<String>,
+}
+
+impl DualStack {
+    /// Applies `next` to the requests for which `cutover` returns true, and
+    /// `current` to the others.
+    pub fn new<F>(current: CORS, next: CORS, cutover: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        DualStack {
+            current: current,
+            next: next,
+            cutover: Box::new(cutover),
+            vary: vec![],
+        }
+    }
+
+    /// Adds `headers` to the `Vary` header of responses, for the request
+    /// headers `cutover` reads.
+    pub fn varying_on(mut self, headers: &[&str]) -> Self {
+        self.vary.extend(headers.iter().map(|header| (*header).to_owned()));
+        self
+    }
+
+    /// Applies `next` to the requests with a `name` header equal to `value`,
+    /// like `X-Api-Version: 2`, and `current` to the others.
+    ///
+    /// Browsers don't send custom headers on preflights, but list them in
+    /// `Access-Control-Request-Headers`: preflights listing `name` use
+    /// `next` too.
+    pub fn header(current: CORS, next: CORS, name: &str, value: &str) -> Self {
+        let vary = [name, "Access-Control-Request-Headers"];
+        let (name, value) = (name.to_owned(), value.to_owned());
+        let stack = DualStack::new(current, next, move |request: &Request| {
+            let headers = request.headers();
+            if headers.get(&name).any(|v| v.trim() == value) {
+                return true;
+            }
+            headers
+                .get_one("Access-Control-Request-Headers")
+                .map_or(false, |requested| {
+                    requested
+                        .split(',')
+                        .any(|header| header.trim().eq_ignore_ascii_case(&name))
+                })
+        });
+        stack.varying_on(&vary)
+    }
+
+    /// The policy applied to `request`.
+    pub fn policy_for(&self, request: &Request) -> &CORS {
+        if (self.cutover)(request) {
+            &self.next
+        } else {
+            &self.current
+        }
+    }
+}
+
+impl Fairing for DualStack {
+    fn info(&self) -> Info {
+        let attach = self.current.attaches() || self.next.attaches();
+        Info {
+            name: "CORS support (dual stack)",
+            kind: if attach {
+                Kind::Attach | Kind::Response
+            } else {
+                Kind::Response
+            },
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        // Both policies are checked, so that each reports its mismatches.
+        let current_passes = self.current.passes_attach_self_test();
+        if !(self.next.passes_attach_self_test() && current_passes) {
+            return Err(rocket);
+        }
+        // Both policies usually declare the same paths, whose routes would
+        // collide if each policy mounted its own.
+        let mounting: Vec<&CORS> = [&self.current, &self.next]
+            .iter()
+            .cloned()
+            .filter(|cors| cors.preflight_rank.is_some())
+            .collect();
+        match mounting.first().and_then(|cors| cors.preflight_rank) {
+            Some(rank) => {
+                let templates = mounting
+                    .iter()
+                    .flat_map(|&cors| cors.templates.iter())
+                    .filter_map(|template| template.as_ref());
+                Ok(rocket.mount("/", preflight::routes_of(templates, rank)))
+            }
+            None => Ok(rocket),
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let policy = self.policy_for(request);
+        policy.on_response(request, response);
+        if !self.vary.is_empty() {
+            let vary = Header::new("Vary", self.vary.join(", "));
+            policy.merge_vary(response, &vary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DualStack;
+    use {AllowedOrigins, CORS};
+    use rocket;
+    use rocket::http::{Header, Method, Status};
+    use rocket::local::Client;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn cutover_on_header() {
+        let v1 = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://old.example.com"]));
+        let v2 = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://new.example.com"]));
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(DualStack::header(v1, v2, "X-Api-Version", "2"));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let allowed = |origin: &str, version: Option<&str>| {
+            let mut request = client
+                .get("/endpoint")
+                .header(Header::new("Origin", origin.to_owned()));
+            if let Some(version) = version {
+                request = request.header(Header::new("X-Api-Version", version.to_owned()));
+            }
+            request
+                .dispatch()
+                .headers()
+                .contains("Access-Control-Allow-Origin")
+        };
+
+        assert!(allowed("https://old.example.com", None));
+        assert!(!allowed("https://new.example.com", None));
+        assert!(allowed("https://new.example.com", Some("2")));
+        assert!(!allowed("https://old.example.com", Some("2")));
+
+        let response = client
+            .options("/endpoint")
+            .header(Header::new("Origin", "https://new.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .header(Header::new("Access-Control-Request-Headers", "x-api-version"))
+            .dispatch();
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+
+        let response = client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://old.example.com"))
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Vary"),
+            Some("Origin, X-Api-Version, Access-Control-Request-Headers")
+        );
+    }
+
+    #[test]
+    fn mounts_preflight_routes() {
+        let v1 = cors!("/api/v1/items" => Method::Get);
+        let v2 = cors!("/api/v2/items" => Method::Get).mount_preflight_routes(-10);
+        let rocket = rocket::ignite().attach(DualStack::header(v1, v2, "X-Api-Version", "2"));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let response = client
+            .options("/api/v2/items")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .header(Header::new("Access-Control-Request-Headers", "x-api-version"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn mounts_shared_preflight_routes_once() {
+        let v1 = cors!("/api/items" => Method::Get).mount_preflight_routes(-10);
+        let v2 = cors!("/api/items" => Method::Get, Method::Put; "/api/users" => Method::Get)
+            .mount_preflight_routes(-10);
+        let rocket = rocket::ignite().attach(DualStack::header(v1, v2, "X-Api-Version", "2"));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        for path in &["/api/items", "/api/users"] {
+            let response = client
+                .options(*path)
+                .header(Header::new("Origin", "https://example.com"))
+                .header(Header::new("Access-Control-Request-Method", "GET"))
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert!(response.headers().contains("Access-Control-Allow-Origin"));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Detection of origins getting denied at an unusual rate.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::monitor::DenialMonitor;
+//! use std::time::Duration;
+//!
+//! let monitor = DenialMonitor::new(
+//!     100,
+//!     Duration::from_secs(60),
+//!     |origin: &str, count: usize, request_id: Option<&str>| {
+//!         println!("{} denied {} times in the last minute", origin, count);
+//!         println!("last denied request: {:?}", request_id);
+//!     },
+//! ).correlation_header("X-Request-Id");
+//! let cors = cors!("/endpoint" => Method::Get).response_hook(monitor);
+//! # }
+//! ```
+
+use Decision;
+use clock::{Clock, SystemClock};
+use hooks::ResponseHook;
+use rates::{Counts, DEFAULT_MAX_ORIGINS};
+use rocket::Request;
+use rocket::http::Header;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A response hook counting the denied requests of each origin in a sliding
+/// window, and invoking a callback with the origin and the count when it
+/// goes over a threshold. The callback also gets the correlation id of the
+/// denied request that went over it, if `correlation_header` is set and the
+/// request has the header.
+///
+/// The callback is invoked once each time the threshold is crossed, not on
+/// every denial above it. Only requests denied because of their origin,
+/// their method or a malformed preflight are counted: those on paths no
+/// endpoint matches are normal traffic. Requests without an `Origin` header
+/// are ignored.
+///
+/// Denials are counted like `rates::OriginRates` counts requests, for up to
+/// `DEFAULT_MAX_ORIGINS` origins unless set with `max_origins`.
+pub struct DenialMonitor {
+    threshold: usize,
+    callback: Box<Fn(&str, usize, Option<&str>) + Send + Sync>,
+    clock: Box<Clock>,
+    counts: Mutex<Counts>,
+    correlation_header: Option<String>,
+}
+
+impl DenialMonitor {
+    /// Creates a monitor invoking `callback` when an origin is denied more
+    /// than `threshold` times within `window`.
+    pub fn new<F>(threshold: usize, window: Duration, callback: F) -> Self
+    where
+        F: Fn(&str, usize, Option<&str>) + Send + Sync + 'static,
+    {
+        DenialMonitor {
+            threshold: threshold,
+            callback: Box::new(callback),
+            clock: Box::new(SystemClock),
+            counts: Mutex::new(Counts::new(window, DEFAULT_MAX_ORIGINS)),
+            correlation_header: None,
+        }
+    }
+
+    /// Passes the value of the `name` request header, like "X-Request-Id",
+    /// to the callback, so that bursts can be tied to client traces. It is
+    /// usually the header given to `CORS::correlation_header`.
+    pub fn correlation_header(mut self, name: &str) -> Self {
+        self.correlation_header = Some(name.to_owned());
+        self
+    }
+
+    /// Uses `clock` instead of the system clock to time denials.
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the most origins counted separately.
+    pub fn max_origins(self, max_origins: usize) -> Self {
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.set_max_origins(max_origins);
+        }
+        self
+    }
+
+    /// Counts a denial of `origin`, by the request with `correlation_id`.
+    fn record(&self, origin: &str, correlation_id: Option<&str>) {
+        let now = self.clock.now();
+        let count = {
+            let mut counts = match self.counts.lock() {
+                Ok(counts) => counts,
+                Err(_) => return,
+            };
+            let tally = counts.record(origin, now);
+            let count = tally.count();
+            if count <= self.threshold {
+                tally.reported = false;
+                return;
+            }
+            if tally.reported {
+                return;
+            }
+            tally.reported = true;
+            count
+        };
+        // Called without holding the lock, so that the callback can take its
+        // time.
+        (self.callback)(origin, count, correlation_id);
+    }
+}
+
+impl ResponseHook for DenialMonitor {
+    fn on_headers(&self, request: &Request, decision: &Decision, _: &mut Vec<Header<'static>>) {
+        match *decision {
+            Decision::OriginNotAllowed |
+            Decision::MethodNotAllowed |
+            Decision::MalformedPreflight => {}
+            _ => return,
+        }
+        let headers = request.headers();
+        if let Some(origin) = headers.get_one("Origin") {
+            let correlation_id = self.correlation_header
+                .as_ref()
+                .and_then(|name| headers.get_one(name));
+            self.record(origin, correlation_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DenialMonitor;
+    use {AllowedOrigins, CORS};
+    use clock::MockClock;
+    use rocket;
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn reports_bursts() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let sink = reports.clone();
+        let clock = MockClock::new();
+        let monitor = DenialMonitor::new(2, Duration::from_secs(60), move |origin: &str, count, _| {
+            sink.lock().unwrap().push((origin.to_owned(), count));
+        }).with_clock(clock.clone());
+
+        monitor.record("https://example.com", None);
+        for _ in 0..4 {
+            monitor.record("https://evil.com", None);
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(*reports.lock().unwrap(), vec![("https://evil.com".to_owned(), 3)]);
+
+        // Once the window has passed, the count starts over.
+        clock.advance(Duration::from_secs(120));
+        for _ in 0..3 {
+            monitor.record("https://evil.com", None);
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(reports.lock().unwrap().len(), 2);
+    }
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn ignores_unconfigured_paths() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let sink = reports.clone();
+        let monitor = DenialMonitor::new(1, Duration::from_secs(60), move |origin: &str, _, _| {
+            sink.lock().unwrap().push(origin.to_owned());
+        });
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+            .response_hook(monitor);
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        for path in &["/other", "/other", "/endpoint", "/endpoint"] {
+            client
+                .get(*path)
+                .header(Header::new("Origin", "https://evil.com"))
+                .dispatch();
+        }
+        assert_eq!(*reports.lock().unwrap(), vec!["https://evil.com".to_owned()]);
+    }
+
+    #[test]
+    fn reports_correlation_ids() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let sink = reports.clone();
+        let monitor = DenialMonitor::new(1, Duration::from_secs(60), move |_: &str, _, id| {
+            sink.lock().unwrap().push(id.map(|id| id.to_owned()));
+        }).correlation_header("X-Request-Id");
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+            .response_hook(monitor);
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        for id in &["abc-1", "abc-2", "abc-3"] {
+            client
+                .get("/endpoint")
+                .header(Header::new("Origin", "https://evil.com"))
+                .header(Header::new("X-Request-Id", *id))
+                .dispatch();
+        }
+        assert_eq!(*reports.lock().unwrap(), vec![Some("abc-2".to_owned())]);
+    }
+}
@@ -0,0 +1,233 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Describes the CORS policy in an existing OpenAPI document, available with
+//! the `openapi` feature.
+//!
+//! Each endpoint path gets an `x-cors` extension like:
+//! ```json
+//! "x-cors": {
+//!   "allowOrigins": ["https://app.example.com"],
+//!   "blockedOrigins": [],
+//!   "allowCredentials": true,
+//!   "allowMethods": ["GET", "HEAD", "PUT"],
+//!   "allowHeaders": ["accept", "accept-language", "authorization", "content-type"],
+//!   "exposeHeaders": ["ETag"]
+//! }
+//! ```
+//!
+//! `allowOrigins` is `["*"]` when any origin is allowed, and lists the
+//! entries or regexes of the allowed origins otherwise. The settings of each
+//! endpoint replace those of the fairing.
+
+use {AllowedHeaders, RequestMeta, CORS};
+use rocket::http::Method;
+use serde_json::{Map, Value};
+use template::{PathTemplate, Segment};
+
+/// Converts a path template into an OpenAPI path: "/api/:user" becomes
+/// "/api/{user}".
+pub fn openapi_path(template: &PathTemplate) -> String {
+    if template.segments().is_empty() {
+        return "/".to_owned();
+    }
+    template
+        .segments()
+        .iter()
+        .map(|segment| match *segment {
+            Segment::Literal(ref value) => format!("/{}", value),
+            Segment::Variable(ref name) => format!("/{{{}}}", name),
+        })
+        .collect()
+}
+
+/// Adds an `x-cors` extension to the path item of every endpoint in
+/// `document`, creating missing path items.
+/// The fields of extensions left by a previous merge are replaced, keeping
+/// other fields; the methods and exposed headers of endpoints sharing a path
+/// are merged.
+/// Does nothing if `document` or its `paths` aren't JSON objects.
+pub fn merge_into(cors: &CORS, document: &mut Value) {
+    let mut described: Vec<String> = vec![];
+    for_each_path_item(cors, document, |path, item, endpoint| {
+        let extension = item.entry("x-cors").or_insert_with(|| json!({}));
+        if !extension.is_object() {
+            *extension = json!({});
+        }
+        if !described.iter().any(|described| described == path) {
+            described.push(path.to_owned());
+            let allow_headers = match *cors.allowed_headers_of(Some(endpoint)) {
+                AllowedHeaders::Some(ref headers) => headers.clone(),
+                AllowedHeaders::All | AllowedHeaders::Echo => vec!["*".to_owned()],
+            };
+            let allow_credentials = cors.endpoint_policies[endpoint]
+                .allow_credentials
+                .unwrap_or(cors.allow_credentials);
+            let fields = json!({
+                "allowOrigins": cors.allowed_origins_of(Some(endpoint)).entries(),
+                "blockedOrigins": cors.blocked_origins.entries(),
+                "allowCredentials": allow_credentials,
+                "allowMethods": [],
+                "allowHeaders": allow_headers,
+                "exposeHeaders": [],
+            });
+            if let (Some(extension), Value::Object(fields)) = (extension.as_object_mut(), fields) {
+                extension.extend(fields);
+            }
+        }
+        let methods = cors.with_implied_methods(&cors.allowed_endpoints[endpoint].0);
+        let methods = methods.iter().map(|method| method.as_str().to_owned());
+        merge_list(extension, "allowMethods", methods);
+        let meta = RequestMeta::new(Method::Get, &cors.allowed_endpoints[endpoint].1);
+        merge_list(extension, "exposeHeaders", cors.exposed_headers(&meta, Some(endpoint)));
+    });
+}
+
+/// Adds the missing `values` to the `key` list of `extension`.
+fn merge_list<I: IntoIterator<Item = String>>(extension: &mut Value, key: &str, values: I) {
+    if let Some(list) = extension.get_mut(key).and_then(|list| list.as_array_mut()) {
+        for value in values {
+            let value = Value::from(value);
+            if !list.contains(&value) {
+                list.push(value);
+            }
+        }
+    }
+}
+
+/// Adds an `options` operation describing the preflight response to the path
+/// item of every endpoint in `document`, keeping already documented ones.
+/// Does nothing if `document` or its `paths` aren't JSON objects.
+pub fn add_preflight_operations(cors: &CORS, document: &mut Value) {
+    for_each_path_item(cors, document, |_, item, _| {
+        item.entry("options").or_insert_with(|| {
+            let header = json!({ "schema": { "type": "string" } });
+            let mut responses = Map::new();
+            responses.insert(
+                cors.preflight_status.code.to_string(),
+                json!({
+                    "description": "Cross-origin access is granted.",
+                    "headers": {
+                        "Access-Control-Allow-Origin": header,
+                        "Access-Control-Allow-Methods": header,
+                        "Access-Control-Allow-Headers": header,
+                    }
+                }),
+            );
+            json!({
+                "summary": "CORS preflight",
+                "responses": responses,
+            })
+        });
+    });
+}
+
+fn for_each_path_item<F>(cors: &CORS, document: &mut Value, mut f: F)
+where
+    F: FnMut(&str, &mut Map<String, Value>, usize),
+{
+    let paths = match document.as_object_mut() {
+        Some(document) => document.entry("paths").or_insert_with(|| json!({})),
+        None => return,
+    };
+    let paths = match paths.as_object_mut() {
+        Some(paths) => paths,
+        None => return,
+    };
+    for (index, &(_, ref path)) in cors.endpoints().iter().enumerate() {
+        let template = match PathTemplate::parse(path) {
+            Ok(template) => template,
+            Err(_) => continue,
+        };
+        let path = openapi_path(&template);
+        let item = paths.entry(path.clone()).or_insert_with(|| json!({}));
+        if let Some(item) = item.as_object_mut() {
+            f(&path, item, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_preflight_operations, merge_into};
+    use {AllowedOrigins, CORS};
+    use policy::EndpointPolicy;
+    use rocket::http::Method;
+
+    #[test]
+    fn extensions() {
+        let cors = cors!("/api/:user" => Method::Get; "/api/:user" => Method::Put, Method::Get)
+            .expose_headers(&["ETag"]);
+        let mut document = json!({
+            "openapi": "3.0.0",
+            "paths": { "/api/{user}": { "get": { "summary": "Get a user" } } }
+        });
+        merge_into(&cors, &mut document);
+        let item = &document["paths"]["/api/{user}"];
+        assert_eq!(item["get"]["summary"], "Get a user");
+        assert_eq!(item["x-cors"]["allowOrigins"], json!(["*"]));
+        assert_eq!(item["x-cors"]["allowCredentials"], false);
+        assert_eq!(item["x-cors"]["allowMethods"], json!(["GET", "HEAD", "PUT"]));
+        assert_eq!(item["x-cors"]["exposeHeaders"], json!(["ETag"]));
+    }
+
+    #[test]
+    fn restricted_extensions() {
+        let cors = cors!("/api/:user" => Method::Get; "/admin" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]))
+            .blocked_origins(&["https://old.example.com"])
+            .policy_for(
+                "/admin",
+                EndpointPolicy::new()
+                    .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]))
+                    .allow_credentials(true),
+            );
+        let mut document = json!({ "openapi": "3.0.0" });
+        merge_into(&cors, &mut document);
+        let extension = &document["paths"]["/api/{user}"]["x-cors"];
+        assert_eq!(extension["allowOrigins"], json!(["https://app.example.com"]));
+        assert_eq!(extension["blockedOrigins"], json!(["https://old.example.com"]));
+        assert_eq!(extension["allowCredentials"], false);
+        let extension = &document["paths"]["/admin"]["x-cors"];
+        assert_eq!(extension["allowOrigins"], json!(["https://admin.example.com"]));
+        assert_eq!(extension["allowCredentials"], true);
+    }
+
+    #[test]
+    fn preflight_operations() {
+        let cors = cors!("/endpoint" => Method::Get);
+        let mut document = json!({ "openapi": "3.0.0" });
+        add_preflight_operations(&cors, &mut document);
+        assert_eq!(
+            document["paths"]["/endpoint"]["options"]["summary"],
+            "CORS preflight"
+        );
+    }
+
+    #[test]
+    fn stale_extensions() {
+        let mut document = json!({
+            "openapi": "3.0.0",
+            "paths": {
+                "/api/{user}": {
+                    "x-cors": {
+                        "allowOrigins": ["https://old.example.com"],
+                        "allowCredentials": true,
+                        "allowMethods": ["DELETE"],
+                        "x-owner": "accounts"
+                    }
+                }
+            }
+        });
+        let cors = cors!("/api/:user" => Method::Get; "/api/:user" => Method::Put)
+            .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]));
+        merge_into(&cors, &mut document);
+        merge_into(&cors, &mut document);
+        let extension = &document["paths"]["/api/{user}"]["x-cors"];
+        assert_eq!(extension["allowOrigins"], json!(["https://app.example.com"]));
+        assert_eq!(extension["allowCredentials"], false);
+        assert_eq!(extension["allowMethods"], json!(["GET", "HEAD", "PUT"]));
+        assert_eq!(extension["x-owner"], "accounts");
+    }
+}
@@ -0,0 +1,315 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Typed origins, so that they are compared by scheme, host and port rather
+//! than as strings.
+//!
+//! Internationalized domain names are normalized to punycode, so that
+//! "https://BÜRO.example" is the same origin as its punycode form.
+
+use idna;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An origin, like "https://example.com:8443".
+///
+/// The scheme and host are lowercased, the host is converted to punycode, and
+/// the port is kept only if it isn't the default one of the scheme, so that
+/// "https://EXAMPLE.com:443" is the same origin as "https://example.com".
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Origin {
+    scheme: String,
+    host: String,
+    port: Option<u16>,
+}
+
+/// The reasons an origin can be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OriginError {
+    /// There is no "scheme://" part.
+    MissingScheme,
+    /// The scheme contains invalid characters.
+    InvalidScheme,
+    /// The host is empty or contains invalid characters.
+    InvalidHost,
+    /// The port isn't a number up to 65535.
+    InvalidPort,
+    /// Something follows the host and port, like a path or credentials.
+    TrailingCharacters,
+}
+
+impl fmt::Display for OriginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OriginError::MissingScheme => write!(f, "missing scheme"),
+            OriginError::InvalidScheme => write!(f, "invalid scheme"),
+            OriginError::InvalidHost => write!(f, "invalid host"),
+            OriginError::InvalidPort => write!(f, "invalid port"),
+            OriginError::TrailingCharacters => write!(f, "unexpected characters after the host"),
+        }
+    }
+}
+
+impl Error for OriginError {
+    fn description(&self) -> &str {
+        "invalid origin"
+    }
+}
+
+/// The port used by `scheme` when none is given.
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        _ => None,
+    }
+}
+
+impl Origin {
+    /// Parses an origin like "https://example.com:8443".
+    pub fn parse(origin: &str) -> Result<Self, OriginError> {
+        OriginRef::parse(origin).map(|origin| Origin {
+            scheme: origin.scheme.into_owned(),
+            host: origin.host.into_owned(),
+            port: origin.port,
+        })
+    }
+
+    /// The scheme, like "https".
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The host, like "example.com".
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port, if it isn't the default one of the scheme.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The port, or the default one of the scheme.
+    pub fn effective_port(&self) -> Option<u16> {
+        self.port.or_else(|| default_port(&self.scheme))
+    }
+}
+
+impl FromStr for Origin {
+    type Err = OriginError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Origin::parse(s)
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// An origin borrowing its scheme and host from the string it is parsed
+/// from when they are already normalized, so that the `Origin` headers of
+/// requests are checked without copies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct OriginRef<'a> {
+    scheme: Cow<'a, str>,
+    host: Cow<'a, str>,
+    port: Option<u16>,
+}
+
+impl<'a> OriginRef<'a> {
+    /// Parses an origin like "https://example.com:8443".
+    pub fn parse(origin: &'a str) -> Result<Self, OriginError> {
+        match origin.find("://") {
+            Some(pos) => OriginRef::from_parts(&origin[..pos], &origin[pos + 3..]),
+            None => Err(OriginError::MissingScheme),
+        }
+    }
+
+    /// Parses the origin with `scheme`, and `rest` after "://".
+    pub fn from_parts(scheme: &'a str, rest: &'a str) -> Result<Self, OriginError> {
+        let valid_scheme = scheme.chars().next().map_or(false, |c| c.is_alphabetic()) &&
+            scheme.chars().all(|c| {
+                c.is_ascii() && (c.is_alphanumeric() || c == '+' || c == '-' || c == '.')
+            });
+        if !valid_scheme {
+            return Err(OriginError::InvalidScheme);
+        }
+        if rest.contains(|c| c == '/' || c == '?' || c == '#' || c == '@') {
+            return Err(OriginError::TrailingCharacters);
+        }
+
+        // IPv6 addresses are bracketed, and contain ':'.
+        let port_start = if rest.starts_with('[') {
+            match rest.find(']') {
+                Some(end) if end + 1 == rest.len() => None,
+                Some(end) if rest[end + 1..].starts_with(':') => Some(end + 1),
+                Some(_) => return Err(OriginError::TrailingCharacters),
+                None => return Err(OriginError::InvalidHost),
+            }
+        } else {
+            rest.find(':')
+        };
+        let (host, port) = match port_start {
+            Some(pos) => {
+                let port = rest[pos + 1..].parse::<u16>().map_err(|_| OriginError::InvalidPort)?;
+                (&rest[..pos], Some(port))
+            }
+            None => (rest, None),
+        };
+        let host = if host.is_ascii() {
+            lowercase(host)
+        } else {
+            Cow::Owned(idna::domain_to_ascii(host).map_err(|_| OriginError::InvalidHost)?)
+        };
+        let valid_host = if host.starts_with('[') {
+            host.len() > 2 &&
+                host[1..host.len() - 1]
+                    .chars()
+                    .all(|c| c.is_digit(16) || c == ':' || c == '.')
+        } else {
+            !host.is_empty() &&
+                host.chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '.' || c == '_')
+        };
+        if !valid_host {
+            return Err(OriginError::InvalidHost);
+        }
+
+        let scheme = lowercase(scheme);
+        let port = match port {
+            Some(port) if Some(port) == default_port(&scheme) => None,
+            port => port,
+        };
+        Ok(OriginRef {
+            host: host,
+            scheme: scheme,
+            port: port,
+        })
+    }
+
+    /// The same origin, with its own copy of the scheme and host.
+    pub fn into_owned(self) -> OriginRef<'static> {
+        OriginRef {
+            scheme: Cow::Owned(self.scheme.into_owned()),
+            host: Cow::Owned(self.host.into_owned()),
+            port: self.port,
+        }
+    }
+
+    /// The scheme, like "https".
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The host, like "example.com".
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port, if it isn't the default one of the scheme.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The port, or the default one of the scheme.
+    pub fn effective_port(&self) -> Option<u16> {
+        self.port.or_else(|| default_port(&self.scheme))
+    }
+}
+
+impl<'a> fmt::Display for OriginRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// `s` in lowercase, only copied if it has uppercase letters.
+fn lowercase(s: &str) -> Cow<str> {
+    if s.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(s.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Origin, OriginError, OriginRef};
+    use std::borrow::Cow;
+
+    #[test]
+    fn parse() {
+        let origin = Origin::parse("HTTPS://Example.com:8443").unwrap();
+        assert_eq!(origin.scheme(), "https");
+        assert_eq!(origin.host(), "example.com");
+        assert_eq!(origin.port(), Some(8443));
+        assert_eq!(origin.to_string(), "https://example.com:8443");
+
+        let origin = Origin::parse("https://example.com:443").unwrap();
+        assert_eq!(origin, Origin::parse("https://example.com").unwrap());
+        assert_eq!(origin.effective_port(), Some(443));
+        assert_eq!(
+            Origin::parse("http://[::1]:8000").unwrap().to_string(),
+            "http://[::1]:8000"
+        );
+    }
+
+    #[test]
+    fn internationalized_domain_names() {
+        let origin = Origin::parse("https://BÜRO.example").unwrap();
+        assert!(origin.host().starts_with("xn--"));
+        assert!(origin.host().is_ascii());
+        assert_eq!(origin, Origin::parse("https://büro.example").unwrap());
+        assert_eq!(Origin::parse(&origin.to_string()).unwrap(), origin);
+    }
+
+    #[test]
+    fn distinct_origins() {
+        let https = Origin::parse("https://example.com").unwrap();
+        assert!(https != Origin::parse("http://example.com").unwrap());
+        assert!(https != Origin::parse("https://example.com:8443").unwrap());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(Origin::parse("example.com"), Err(OriginError::MissingScheme));
+        assert_eq!(Origin::parse("1http://example.com"), Err(OriginError::InvalidScheme));
+        assert_eq!(Origin::parse("https://"), Err(OriginError::InvalidHost));
+        assert_eq!(Origin::parse("https://example.com:x"), Err(OriginError::InvalidPort));
+        assert_eq!(
+            Origin::parse("https://evil.com:@example.com"),
+            Err(OriginError::TrailingCharacters)
+        );
+        assert_eq!(
+            Origin::parse("https://example.com/"),
+            Err(OriginError::TrailingCharacters)
+        );
+    }
+
+    #[test]
+    fn borrows_normalized_origins() {
+        let origin = OriginRef::parse("https://example.com:8443").unwrap();
+        match (&origin.scheme, &origin.host) {
+            (&Cow::Borrowed("https"), &Cow::Borrowed("example.com")) => {}
+            parts => panic!("copied {:?}", parts),
+        }
+        let origin = OriginRef::parse("HTTPS://Example.com").unwrap();
+        assert_eq!(origin.to_string(), "https://example.com");
+        assert_eq!(origin, OriginRef::parse("https://example.com:443").unwrap());
+    }
+}
@@ -0,0 +1,348 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Origins granted CORS access.
+
+use origin::OriginRef;
+#[cfg(feature = "regex")]
+use regex::{self, Regex, RegexBuilder};
+
+/// The most memory a compiled origin regex may use, in bytes, so that a
+/// pattern from an untrusted configuration can't exhaust it.
+#[cfg(feature = "regex")]
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// Longer origins aren't matched against regexes. A host can't be longer
+/// than 253 characters.
+#[cfg(feature = "regex")]
+const MAX_REGEX_ORIGIN_LENGTH: usize = 300;
+
+/// The origins granted CORS access, checked against the `Origin` request
+/// header.
+#[derive(Clone, Debug)]
+pub enum AllowedOrigins {
+    /// Any origin, answered with `Access-Control-Allow-Origin: *`. This is the
+    /// default.
+    All,
+    /// Only these origins, like "https://app.example.com". Requests from
+    /// other origins or without an `Origin` header are denied, and allowed
+    /// ones are answered with their own origin.
+    ///
+    /// Origins are the same if they have the same scheme, host and port:
+    /// "https://example.com" isn't "http://example.com" nor
+    /// "https://example.com:8443", but it is "https://example.com:443".
+    /// An entry can also be a pattern: "https://*.example.com" matches the
+    /// origins with the same scheme and port of every subdomain of
+    /// "example.com", but not "example.com" itself, and
+    /// "https://example.com:*" matches any port on "example.com".
+    Some(OriginList),
+    /// The origins entirely matched by one of these regexes, available with
+    /// the `regex` feature. Other origins are denied, and allowed ones are
+    /// answered with their own origin. Build it with `AllowedOrigins::regex`.
+    #[cfg(feature = "regex")]
+    Regex(Vec<Regex>),
+}
+
+impl AllowedOrigins {
+    /// Only allows `origins`.
+    pub fn some(origins: &[&str]) -> Self {
+        AllowedOrigins::Some(OriginList::new(origins))
+    }
+
+    /// Only allows the origins entirely matched by one of `patterns`, like
+    /// `https://pr-\d+\.preview\.example\.com`. The patterns are compiled
+    /// once, here.
+    ///
+    /// Matching takes a time linear in the length of the origin whatever the
+    /// pattern, since the regex engine doesn't backtrack, and origins longer
+    /// than any real one are denied without being matched. Patterns whose
+    /// compiled form would use more than 64 KiB are refused with
+    /// `regex::Error::CompiledTooBig`.
+    #[cfg(feature = "regex")]
+    pub fn regex(patterns: &[&str]) -> Result<Self, regex::Error> {
+        let regexes: Result<Vec<Regex>, regex::Error> = patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(&format!("^(?:{})$", pattern))
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .dfa_size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+            })
+            .collect();
+        regexes.map(AllowedOrigins::Regex)
+    }
+
+    /// Returns true if a request with `origin` as `Origin` header is allowed.
+    pub fn allows(&self, origin: Option<&str>) -> bool {
+        let parsed = origin.and_then(|origin| OriginRef::parse(origin).ok());
+        self.allows_parsed(origin, parsed.as_ref())
+    }
+
+    /// Like `allows`, with `parsed` the origin if it is valid, parsed once
+    /// for all the checks of a request. Only regexes match `origin` itself.
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+    pub(crate) fn allows_parsed(&self, origin: Option<&str>, parsed: Option<&OriginRef>) -> bool {
+        match *self {
+            AllowedOrigins::All => true,
+            AllowedOrigins::Some(ref origins) => parsed.map_or(false, |origin| {
+                origins.matches(origin)
+            }),
+            #[cfg(feature = "regex")]
+            AllowedOrigins::Regex(ref regexes) => origin.map_or(false, |origin| {
+                origin.len() <= MAX_REGEX_ORIGIN_LENGTH &&
+                    regexes.iter().any(|regex| regex.is_match(origin))
+            }),
+        }
+    }
+
+    /// The allowed origins, as written in the configuration: "*" for any
+    /// origin, or the entries or regex patterns.
+    pub(crate) fn entries(&self) -> Vec<String> {
+        match *self {
+            AllowedOrigins::All => vec!["*".to_owned()],
+            AllowedOrigins::Some(ref origins) => origins.entries().to_vec(),
+            #[cfg(feature = "regex")]
+            AllowedOrigins::Regex(ref regexes) => {
+                regexes.iter().map(|regex| regex.as_str().to_owned()).collect()
+            }
+        }
+    }
+}
+
+impl PartialEq for AllowedOrigins {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&AllowedOrigins::All, &AllowedOrigins::All) => true,
+            (&AllowedOrigins::Some(ref a), &AllowedOrigins::Some(ref b)) => a == b,
+            // Regexes are equal if they were compiled from the same patterns.
+            #[cfg(feature = "regex")]
+            (&AllowedOrigins::Regex(ref a), &AllowedOrigins::Regex(ref b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.as_str() == b.as_str())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AllowedOrigins {}
+
+/// Origin entries, parsed once when the policy is built so that requests
+/// are matched against them without parsing them again.
+#[derive(Clone, Debug, Default)]
+pub struct OriginList {
+    entries: Vec<String>,
+    /// The entries that can match an origin, parsed.
+    parsed: Vec<Entry<'static>>,
+}
+
+impl OriginList {
+    /// Parses `entries`. Those matching no origin are kept as written, but
+    /// never match.
+    pub fn new(entries: &[&str]) -> Self {
+        OriginList {
+            entries: entries.iter().map(|entry| (*entry).to_owned()).collect(),
+            parsed: entries
+                .iter()
+                .filter_map(|entry| Entry::parse(entry))
+                .map(Entry::into_owned)
+                .collect(),
+        }
+    }
+
+    /// The entries, as written.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Returns true if there is no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries that can match an origin, parsed.
+    pub(crate) fn parsed(&self) -> &[Entry<'static>] {
+        &self.parsed
+    }
+
+    /// Returns true if one of the entries matches `origin`.
+    pub(crate) fn matches(&self, origin: &OriginRef) -> bool {
+        self.parsed.iter().any(|entry| entry.matches(origin))
+    }
+}
+
+impl PartialEq for OriginList {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Eq for OriginList {}
+
+/// An entry of `AllowedOrigins::Some` or of `CORS::blocked_origins`: an
+/// origin, or a pattern matching its subdomains or any of its ports.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry<'a> {
+    /// The origin, without the "*." and ":*" parts of patterns.
+    pub origin: OriginRef<'a>,
+    /// Whether the subdomains of the host match, rather than the host.
+    pub subdomains: bool,
+    /// Whether any port matches.
+    pub any_port: bool,
+}
+
+impl<'a> Entry<'a> {
+    /// Parses `entry`, or returns `None` if it matches no origin.
+    pub fn parse(entry: &'a str) -> Option<Self> {
+        let (entry, any_port) = if entry.ends_with(":*") {
+            (&entry[..entry.len() - 2], true)
+        } else {
+            (entry, false)
+        };
+        // "scheme://*.domain" is parsed as "scheme://domain".
+        let (origin, subdomains) = match entry.find("://*.") {
+            Some(pos) => (OriginRef::from_parts(&entry[..pos], &entry[pos + 5..]), true),
+            None => (OriginRef::parse(entry), false),
+        };
+        origin.ok().map(|origin| Entry {
+            origin: origin,
+            subdomains: subdomains,
+            any_port: any_port,
+        })
+    }
+
+    /// The same entry, with its own copy of the origin.
+    pub fn into_owned(self) -> Entry<'static> {
+        Entry {
+            origin: self.origin.into_owned(),
+            subdomains: self.subdomains,
+            any_port: self.any_port,
+        }
+    }
+
+    /// Returns true if `origin` matches this entry.
+    pub fn matches(&self, origin: &OriginRef) -> bool {
+        let pattern = &self.origin;
+        let host_matches = if self.subdomains {
+            let host = origin.host();
+            host.len() > pattern.host().len() + 1 && host.ends_with(pattern.host()) &&
+                host[..host.len() - pattern.host().len()].ends_with('.')
+        } else {
+            origin.host() == pattern.host()
+        };
+        origin.scheme() == pattern.scheme() && host_matches &&
+            (self.any_port || origin.effective_port() == pattern.effective_port())
+    }
+
+    /// An origin matching this entry, like "https://example.myapp.io" for
+    /// "https://*.myapp.io".
+    pub fn example(&self) -> String {
+        match (self.subdomains, self.origin.port()) {
+            (true, Some(port)) => format!(
+                "{}://example.{}:{}",
+                self.origin.scheme(),
+                self.origin.host(),
+                port
+            ),
+            (true, None) => format!("{}://example.{}", self.origin.scheme(), self.origin.host()),
+            (false, _) => self.origin.to_string(),
+        }
+    }
+}
+
+impl Default for AllowedOrigins {
+    fn default() -> Self {
+        AllowedOrigins::All
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AllowedOrigins, Entry, OriginList};
+    use origin::OriginRef;
+
+    #[test]
+    fn allows() {
+        assert!(AllowedOrigins::All.allows(None));
+        assert!(AllowedOrigins::All.allows(Some("https://example.com")));
+
+        let origins = AllowedOrigins::some(&["https://app.example.com"]);
+        assert!(origins.allows(Some("https://app.example.com")));
+        assert!(origins.allows(Some("https://APP.example.com")));
+        assert!(!origins.allows(Some("https://example.com")));
+        assert!(!origins.allows(Some("http://app.example.com")));
+        assert!(!origins.allows(None));
+    }
+
+    #[test]
+    fn allows_subdomain_patterns() {
+        let origins = AllowedOrigins::some(&["https://*.myapp.io"]);
+        assert!(origins.allows(Some("https://tenant.myapp.io")));
+        assert!(origins.allows(Some("https://a.b.MYAPP.io")));
+        assert!(!origins.allows(Some("https://myapp.io")));
+        assert!(!origins.allows(Some("https://evilmyapp.io")));
+        assert!(!origins.allows(Some("http://tenant.myapp.io")));
+        assert!(!origins.allows(Some("https://tenant.myapp.io:8443")));
+        assert!(!origins.allows(Some("https://evil.com:@x.myapp.io")));
+
+        let origins = AllowedOrigins::some(&["http://*.localhost:8000"]);
+        assert!(origins.allows(Some("http://app.localhost:8000")));
+    }
+
+    #[test]
+    fn entry_examples() {
+        for entry in &["https://*.myapp.io", "http://*.localhost:8000", "https://a.com:*"] {
+            let parsed = Entry::parse(entry).unwrap();
+            let example = parsed.example();
+            let origin = OriginRef::parse(&example).unwrap();
+            assert!(parsed.matches(&origin), "{} for {}", example, entry);
+        }
+        assert!(Entry::parse("https://*.").is_none());
+    }
+
+    #[test]
+    fn origin_lists() {
+        let list = OriginList::new(&["https://*.myapp.io", "myapp.io"]);
+        assert_eq!(list.entries(), &["https://*.myapp.io", "myapp.io"]);
+        assert_eq!(list.parsed().len(), 1);
+        assert!(list.matches(&OriginRef::parse("https://tenant.myapp.io").unwrap()));
+        assert!(!list.matches(&OriginRef::parse("https://myapp.io").unwrap()));
+    }
+
+    #[test]
+    fn compares_schemes_and_ports() {
+        let origins = AllowedOrigins::some(&["https://example.com"]);
+        assert!(origins.allows(Some("https://example.com:443")));
+        assert!(!origins.allows(Some("https://example.com:8443")));
+        assert!(!origins.allows(Some("http://example.com")));
+        assert!(!origins.allows(Some("https://example.com/")));
+
+        let origins = AllowedOrigins::some(&["https://example.com:*", "https://*.example.com:*"]);
+        assert!(origins.allows(Some("https://example.com:8443")));
+        assert!(origins.allows(Some("https://app.example.com:3000")));
+        assert!(!origins.allows(Some("http://example.com:8443")));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn allows_regexes() {
+        let origins = AllowedOrigins::regex(&[r"https://pr-\d+\.preview\.example\.com"]).unwrap();
+        assert!(origins.allows(Some("https://pr-42.preview.example.com")));
+        assert!(!origins.allows(Some("https://pr-42.preview.example.com.evil.com")));
+        assert!(!origins.allows(Some("https://pr-x.preview.example.com")));
+        assert!(AllowedOrigins::regex(&["("]).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn bounds_regexes() {
+        match AllowedOrigins::regex(&[r"https://\w{1000}{1000}\.com"]) {
+            Err(::regex::Error::CompiledTooBig(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let origins = AllowedOrigins::regex(&[r"https://(a|aa)+\.com"]).unwrap();
+        assert!(origins.allows(Some("https://aaaa.com")));
+        let long = format!("https://{}.com", "a".repeat(400));
+        assert!(!origins.allows(Some(&long)));
+    }
+}
@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Settings of some endpoints replacing those of the whole fairing.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::{AllowedOrigins, CORS};
+//! use rocket_cors::policy::EndpointPolicy;
+//!
+//! let cors = cors!("/public/:file" => Method::Get; "/admin/:page" => Method::Get, Method::Post)
+//!     .policy_for(
+//!         "/admin/:page",
+//!         EndpointPolicy::new()
+//!             .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"]))
+//!             .allow_credentials(true),
+//!     );
+//! # }
+//! ```
+
+use {AllowedHeaders, AllowedOrigins};
+use std::time::Duration;
+use switch::Switch;
+
+/// The settings of an endpoint. The ones left unset are those of the
+/// fairing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EndpointPolicy {
+    pub(crate) allowed_origins: Option<AllowedOrigins>,
+    pub(crate) allowed_headers: Option<AllowedHeaders>,
+    pub(crate) expose_headers: Option<Vec<String>>,
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) allow_credentials: Option<bool>,
+    pub(crate) switch: Option<Switch>,
+}
+
+impl EndpointPolicy {
+    /// Creates a policy falling back to the fairing for every setting.
+    pub fn new() -> Self {
+        EndpointPolicy::default()
+    }
+
+    /// Replaces `CORS::allowed_origins`.
+    pub fn allowed_origins(mut self, origins: AllowedOrigins) -> Self {
+        self.allowed_origins = Some(origins);
+        self
+    }
+
+    /// Replaces `CORS::allowed_headers`.
+    pub fn allowed_headers(mut self, headers: AllowedHeaders) -> Self {
+        self.allowed_headers = Some(headers);
+        self
+    }
+
+    /// Replaces `CORS::expose_headers`.
+    pub fn expose_headers(mut self, headers: &[&str]) -> Self {
+        self.expose_headers = Some(headers.iter().map(|h| (*h).to_owned()).collect());
+        self
+    }
+
+    /// Replaces `CORS::max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Replaces `CORS::allow_credentials`, like allowing credentials only on
+    /// the few endpoints that need cookies while the fairing allows none.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = Some(allow);
+        self
+    }
+
+    /// Only grants CORS access while `switch` is enabled. See the `switch`
+    /// module.
+    pub fn enabled_by(mut self, switch: Switch) -> Self {
+        self.switch = Some(switch);
+        self
+    }
+
+    /// Overrides the settings of this policy with those set in `other`.
+    pub(crate) fn merge(&mut self, other: &EndpointPolicy) {
+        if other.allowed_origins.is_some() {
+            self.allowed_origins = other.allowed_origins.clone();
+        }
+        if other.allowed_headers.is_some() {
+            self.allowed_headers = other.allowed_headers.clone();
+        }
+        if other.expose_headers.is_some() {
+            self.expose_headers = other.expose_headers.clone();
+        }
+        if other.max_age.is_some() {
+            self.max_age = other.max_age;
+        }
+        if other.allow_credentials.is_some() {
+            self.allow_credentials = other.allow_credentials;
+        }
+        if other.switch.is_some() {
+            self.switch = other.switch.clone();
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `OPTIONS` routes answering preflights, so that they don't go through
+//! Rocket's 404 catcher before the fairing answers them. `OPTIONS` requests
+//! that aren't preflights are forwarded to the next matching route.
+//!
+//! `CORS::mount_preflight_routes` mounts them when the fairing is attached.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::preflight::DEFAULT_RANK;
+//!
+//! let cors = cors!("/api/:user" => Method::Get, Method::Put);
+//! let rocket = rocket::ignite()
+//!     .mount("/", cors.preflight_routes(DEFAULT_RANK))
+//!     .attach(cors);
+//! # }
+//! ```
+
+use {RequestMeta, CORS};
+use rocket::{Data, Request, Route};
+use rocket::handler::Outcome;
+use rocket::http::Method;
+use template::{PathTemplate, Segment};
+
+/// A rank above the default ranks of Rocket routes, so that the generated
+/// routes never shadow the `OPTIONS` routes of the application.
+pub const DEFAULT_RANK: isize = 100;
+
+/// Answers preflights with an empty response, completed by the fairing.
+fn preflight<'r>(request: &'r Request, data: Data) -> Outcome<'r> {
+    if !RequestMeta::from(request).is_preflight() {
+        return Outcome::Forward(data);
+    }
+    Outcome::from(request, ())
+}
+
+/// The Rocket path of a route matching the same paths as `template`.
+/// Variables are renamed, since template names needn't be identifiers.
+fn route_path(template: &PathTemplate) -> String {
+    if template.segments().is_empty() {
+        return "/".to_owned();
+    }
+    template
+        .segments()
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| match *segment {
+            Segment::Literal(ref value) => format!("/{}", value),
+            Segment::Variable(_) => format!("/<p{}>", index),
+        })
+        .collect()
+}
+
+pub(crate) fn routes(cors: &CORS, rank: isize) -> Vec<Route> {
+    routes_of(cors.templates.iter().filter_map(|t| t.as_ref()), rank)
+}
+
+/// The routes for the distinct paths of `templates`, ranked from `rank` away
+/// from zero.
+pub(crate) fn routes_of<'a, I>(templates: I, rank: isize) -> Vec<Route>
+where
+    I: IntoIterator<Item = &'a PathTemplate>,
+{
+    let mut paths: Vec<String> = vec![];
+    for template in templates {
+        let path = route_path(template);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    // Rocket refuses routes with the same rank that can match the same
+    // request, like "/api/<p1>" and "/api/admin": each route gets its own.
+    let step = if rank < 0 { -1 } else { 1 };
+    paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            Route::ranked(rank + step * index as isize, Method::Options, path, preflight)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::DEFAULT_RANK;
+    use CORS;
+    use exempt::Exempt;
+    use rocket;
+    use rocket::http::{Header, Method, Status};
+    use rocket::local::Client;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+    #[options("/api/admin")]
+    fn admin_options() -> Exempt<Status> {
+        Exempt(Status::NoContent)
+    }
+
+    #[test]
+    fn generated_routes() {
+        let cors = cors!(
+            "/api/:user" => Method::Get;
+            "/api/admin" => Method::Get;
+            "/" => Method::Get
+        );
+        let routes = cors.preflight_routes(DEFAULT_RANK);
+        assert_eq!(routes.len(), 3);
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![admin_options])
+            .mount("/", routes)
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .options("/api/john")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+
+        // The application's own route wins.
+        let response = client.options("/api/admin").dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        // Other requests are forwarded.
+        let response = client.options("/api/john").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    static DISCOVERIES: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    #[options("/api/items")]
+    fn discover() -> &'static str {
+        DISCOVERIES.fetch_add(1, Ordering::SeqCst);
+        "GET, POST"
+    }
+
+    #[test]
+    fn mounted_on_attach() {
+        let cors = cors!("/api/items" => Method::Get, Method::Post).mount_preflight_routes(-10);
+        let rocket = rocket::ignite()
+            .mount("/", routes![discover])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+
+        let response = client
+            .options("/api/items")
+            .header(Header::new("Origin", "https://example.com"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.headers().contains("Access-Control-Allow-Origin"));
+        assert_eq!(DISCOVERIES.load(Ordering::SeqCst), 0);
+
+        let mut response = client.options("/api/items").dispatch();
+        assert_eq!(response.body_string(), Some("GET, POST".to_owned()));
+        assert_eq!(DISCOVERIES.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn ranks_away_from_zero() {
+        let cors = cors!(
+            "/api/:user" => Method::Get;
+            "/api/admin" => Method::Get;
+            "/" => Method::Get
+        );
+        let ranks = |rank| {
+            cors.preflight_routes(rank)
+                .iter()
+                .map(|route| route.rank)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranks(DEFAULT_RANK), vec![100, 101, 102]);
+        assert_eq!(ranks(-10), vec![-10, -11, -12]);
+        assert_eq!(ranks(0), vec![0, 1, 2]);
+    }
+}
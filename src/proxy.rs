@@ -0,0 +1,421 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Generates nginx and Apache configuration snippets equivalent to a CORS
+//! fairing, to keep an edge proxy consistent with the Rocket application.
+//!
+//! The snippets only deal with CORS: nginx `location` blocks still need
+//! their `proxy_pass` directive, and the Apache ones need `mod_headers`,
+//! `mod_setenvif` and `mod_rewrite`. The nginx `map` blocks checking origins
+//! go in the `http` context.
+//! Allowed, blocked and `null` origins are translated into regexes, and
+//! the settings of each endpoint replace those of the fairing. Policies with
+//! an origin validator can't be translated, since it only runs in Rocket.
+
+use {AllowedOrigins, RequestMeta, CORS};
+use origins::Entry;
+use rocket::http::Method;
+use std::error::Error;
+use std::fmt;
+use template::{PathTemplate, Segment};
+
+/// Stands for the origin of requests, to find whether it is echoed.
+const ANY_ORIGIN: &str = "https://origin.invalid";
+
+/// Matches the origins browsers send, so that the others are denied.
+const VALID_ORIGIN: &str = "^[a-z][a-z0-9+.-]*://[^/?#@]+$";
+
+/// The reasons a policy can't be translated into a proxy configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyError {
+    /// Origins are checked by an origin validator, which proxies can't run.
+    OriginValidator,
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProxyError::OriginValidator => write!(f, "origin validators can't be translated"),
+        }
+    }
+}
+
+impl Error for ProxyError {
+    fn description(&self) -> &str {
+        "untranslatable CORS policy"
+    }
+}
+
+/// A regex matching origins, with what they get: `Some` value of
+/// `Access-Control-Allow-Origin`, or `None` if they are denied.
+struct OriginRule {
+    regex: String,
+    ignore_case: bool,
+    allow: Option<&'static str>,
+}
+
+/// Generates nginx `map` blocks checking origins, followed by `location`
+/// blocks adding the CORS headers and answering preflights for every
+/// endpoint of `cors`.
+pub fn nginx(cors: &CORS) -> Result<String, ProxyError> {
+    let mut maps = String::new();
+    let mut out = String::new();
+    for (index, (regex, path, methods, endpoint)) in locations(cors).into_iter().enumerate() {
+        let variable = format!("$cors_origin_{}", index);
+        maps.push_str(&format!("map $http_origin {} {{\n    default \"\";\n", variable));
+        for rule in origin_rules(cors, &path, endpoint)? {
+            let value = match rule.allow {
+                Some("*") => "\"*\"",
+                Some(_) => "$http_origin",
+                None => "\"\"",
+            };
+            match rule.regex.as_str() {
+                "^$" => maps.push_str(&format!("    \"\" {};\n", value)),
+                "^null$" => maps.push_str(&format!("    \"null\" {};\n", value)),
+                regex => {
+                    let flags = if rule.ignore_case { "(?i)" } else { "" };
+                    maps.push_str(&format!("    \"~{}{}\" {};\n", flags, regex, value))
+                }
+            }
+        }
+        maps.push_str("}\n");
+
+        out.push_str(&format!("location ~ {} {{\n", regex));
+        out.push_str("    if ($request_method = OPTIONS) {\n");
+        let preflight = meta(Method::Options, &path);
+        let origin = &variable;
+        push_headers(cors, &mut out, &preflight, endpoint, origin, "        add_header ", ";");
+        out.push_str(&format!("        return {};\n    }}\n", cors.preflight_status.code));
+        out.push_str(&format!("    if ($request_method ~ ^({})$) {{\n", methods));
+        let request = meta(Method::Get, &path);
+        push_headers(cors, &mut out, &request, endpoint, origin, "        add_header ", ";");
+        out.push_str("    }\n}\n");
+    }
+    Ok(maps + &out)
+}
+
+/// Generates Apache `LocationMatch` sections adding the CORS headers and
+/// answering preflights for every endpoint of `cors`.
+pub fn apache(cors: &CORS) -> Result<String, ProxyError> {
+    let mut out = String::new();
+    for (regex, path, methods, endpoint) in locations(cors) {
+        out.push_str(&format!("<LocationMatch \"{}\">\n", regex));
+        // Later directives win, so rules are set from the lowest priority.
+        for rule in origin_rules(cors, &path, endpoint)?.into_iter().rev() {
+            let directive = if rule.ignore_case { "SetEnvIfNoCase" } else { "SetEnvIf" };
+            let variable = match rule.allow {
+                Some("*") => "CORS_ORIGIN=*",
+                Some(_) => "CORS_ORIGIN=$0",
+                None => "!CORS_ORIGIN",
+            };
+            out.push_str(&format!(
+                "    {} Origin \"{}\" {}\n",
+                directive,
+                rule.regex,
+                variable
+            ));
+        }
+        out.push_str(&format!(
+            "    SetEnvIfExpr \"%{{REQUEST_METHOD}} !~ /^({}|OPTIONS)$/\" !CORS_ORIGIN\n",
+            methods
+        ));
+        push_headers(
+            cors,
+            &mut out,
+            &meta(Method::Options, &path),
+            endpoint,
+            "\"%{CORS_ORIGIN}e\"",
+            "    Header always set ",
+            " env=CORS_ORIGIN",
+        );
+        let exposed = cors.exposed_headers(&meta(Method::Get, &path), Some(endpoint));
+        if !exposed.is_empty() {
+            out.push_str(&format!(
+                "    SetEnvIf Request_Method \"^({})$\" CORS_ACTUAL_REQUEST\n",
+                methods
+            ));
+            out.push_str("    SetEnvIfExpr \"-z reqenv('CORS_ORIGIN')\" !CORS_ACTUAL_REQUEST\n");
+            out.push_str(&format!(
+                "    Header always set Access-Control-Expose-Headers \"{}\" {}\n",
+                exposed.join(", "),
+                "env=CORS_ACTUAL_REQUEST"
+            ));
+        }
+        out.push_str("    RewriteEngine On\n");
+        out.push_str("    RewriteCond %{REQUEST_METHOD} OPTIONS\n");
+        out.push_str(&format!(
+            "    RewriteRule ^ - [R={},L]\n",
+            cors.preflight_status.code
+        ));
+        out.push_str("</LocationMatch>\n");
+    }
+    Ok(out)
+}
+
+/// A request using `method` on `path`, from an origin standing for any.
+fn meta(method: Method, path: &str) -> RequestMeta {
+    RequestMeta {
+        origin: Some(ANY_ORIGIN),
+        ..RequestMeta::new(method, path)
+    }
+}
+
+/// The rules checking the origins of requests on `endpoint`, the first
+/// matching one winning.
+fn origin_rules(cors: &CORS, path: &str, endpoint: usize) -> Result<Vec<OriginRule>, ProxyError> {
+    if cors.origin_validator.is_some() {
+        return Err(ProxyError::OriginValidator);
+    }
+    // Whether the origin is echoed, or answered with `*`.
+    let allow = match cors.grant(&meta(Method::Get, path), Some(endpoint)).allow_origin.as_str() {
+        ANY_ORIGIN => "$origin",
+        _ => "*",
+    };
+    let mut rules = vec![];
+    if cors.allow_null_origin {
+        rules.push(OriginRule {
+            regex: "^null$".to_owned(),
+            ignore_case: false,
+            allow: Some(allow),
+        });
+    }
+    for blocked in cors.blocked_origins.parsed() {
+        rules.push(OriginRule {
+            regex: entry_regex(blocked),
+            ignore_case: true,
+            allow: None,
+        });
+    }
+    match *cors.allowed_origins_of(Some(endpoint)) {
+        AllowedOrigins::All => rules.push(OriginRule {
+            regex: VALID_ORIGIN.to_owned(),
+            ignore_case: true,
+            allow: Some(allow),
+        }),
+        AllowedOrigins::Some(ref entries) => {
+            for entry in entries.parsed() {
+                rules.push(OriginRule {
+                    regex: entry_regex(entry),
+                    ignore_case: true,
+                    allow: Some(allow),
+                });
+            }
+        }
+        #[cfg(feature = "regex")]
+        AllowedOrigins::Regex(ref regexes) => for regex in regexes {
+            rules.push(OriginRule {
+                regex: regex.as_str().to_owned(),
+                ignore_case: false,
+                allow: Some(allow),
+            });
+        },
+    }
+    if !cors.require_origin {
+        rules.push(OriginRule {
+            regex: "^$".to_owned(),
+            ignore_case: false,
+            allow: Some("*"),
+        });
+    }
+    Ok(rules)
+}
+
+/// A regex matching the origins `entry` matches, ignoring case.
+fn entry_regex(entry: &Entry) -> String {
+    let origin = &entry.origin;
+    let mut regex = format!("^{}://", escape(origin.scheme()));
+    if entry.subdomains {
+        regex.push_str("([a-z0-9_-]+\\.)+");
+    }
+    regex.push_str(&escape(origin.host()));
+    match (entry.any_port, origin.port(), origin.effective_port()) {
+        (true, _, _) => regex.push_str("(:[0-9]+)?"),
+        (false, Some(port), _) => regex.push_str(&format!(":{}", port)),
+        (false, None, Some(port)) => regex.push_str(&format!("(:{})?", port)),
+        (false, None, None) => {}
+    }
+    regex.push('$');
+    regex
+}
+
+/// Pushes the headers of the response to `meta` on `endpoint`, one per line,
+/// with `origin` as the value of `Access-Control-Allow-Origin`.
+fn push_headers(
+    cors: &CORS,
+    out: &mut String,
+    meta: &RequestMeta,
+    endpoint: usize,
+    origin: &str,
+    prefix: &str,
+    suffix: &str,
+) {
+    let mut grant = cors.grant(meta, Some(endpoint));
+    // Proxies answer every `OPTIONS` request as a preflight.
+    if meta.method == Method::Options {
+        grant.max_age = cors.max_age_of(Some(endpoint));
+    }
+    for header in grant {
+        let value = if header.name() == "Access-Control-Allow-Origin" {
+            origin.to_owned()
+        } else {
+            format!("\"{}\"", header.value())
+        };
+        // Keep the values of `Vary` set by the application.
+        let prefix = match header.name() {
+            "Vary" => prefix.replace(" set ", " merge "),
+            _ => prefix.to_owned(),
+        };
+        out.push_str(&format!("{}{} {}{}\n", prefix, header.name(), value, suffix));
+    }
+}
+
+/// The path regex, one of the matching endpoint paths, the `|` separated
+/// methods and the first endpoint of each distinct endpoint path.
+fn locations(cors: &CORS) -> Vec<(String, String, String, usize)> {
+    let mut locations: Vec<(String, String, Vec<Method>, usize)> = vec![];
+    for (endpoint, &(ref methods, ref path)) in cors.endpoints().iter().enumerate() {
+        let template = match PathTemplate::parse(path) {
+            Ok(template) => template,
+            Err(_) => continue,
+        };
+        let regex = path_regex(&template);
+        let position = locations.iter().position(|&(ref r, _, _, _)| *r == regex);
+        let index = match position {
+            Some(index) => index,
+            None => {
+                locations.push((regex, path.clone(), vec![], endpoint));
+                locations.len() - 1
+            }
+        };
+        for method in cors.with_implied_methods(methods) {
+            if !locations[index].2.contains(&method) {
+                locations[index].2.push(method);
+            }
+        }
+    }
+    locations
+        .into_iter()
+        .map(|(regex, path, methods, endpoint)| {
+            let methods: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+            (regex, path, methods.join("|"), endpoint)
+        })
+        .collect()
+}
+
+/// A regex matching the same request paths as `template`.
+fn path_regex(template: &PathTemplate) -> String {
+    let mut regex = String::from("^");
+    for segment in template.segments() {
+        match *segment {
+            Segment::Literal(ref value) => {
+                regex.push('/');
+                regex.push_str(&escape(value));
+            }
+            Segment::Variable(_) => regex.push_str("/[^/]+"),
+        }
+    }
+    regex.push_str("/?$");
+    regex
+}
+
+/// Escapes the characters of `value` that have a meaning in regexes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apache, nginx, ProxyError};
+    use {AllowedOrigins, CORS};
+    use policy::EndpointPolicy;
+    use rocket::Request;
+    use rocket::http::Method;
+
+    #[test]
+    fn nginx_locations() {
+        let cors = cors!("/api/:user/v1.0" => Method::Get; "/api/:user/v1.0" => Method::Put)
+            .expose_headers(&["ETag"]);
+        let config = nginx(&cors).unwrap();
+        assert_eq!(config.matches("location ~ ").count(), 1);
+        assert!(config.contains("location ~ ^/api/[^/]+/v1\\.0/?$ {\n"));
+        assert!(config.contains("if ($request_method ~ ^(GET|HEAD|PUT)$) {\n"));
+        assert!(config.contains(
+            "map $http_origin $cors_origin_0 {\n    default \"\";\n    \
+             \"~(?i)^[a-z][a-z0-9+.-]*://[^/?#@]+$\" \"*\";\n}\n"
+        ));
+        assert!(config.contains("add_header Access-Control-Allow-Origin $cors_origin_0;\n"));
+        assert!(config.contains("        return 200;\n"));
+        assert!(config.contains("add_header Access-Control-Allow-Methods \"GET, HEAD, PUT\";\n"));
+        // Only on actual requests.
+        assert_eq!(config.matches("add_header Access-Control-Expose-Headers \"ETag\";").count(), 1);
+    }
+
+    #[test]
+    fn apache_locations() {
+        let config = apache(&cors!("/endpoint" => Method::Get).expose_headers(&["ETag"])).unwrap();
+        assert!(config.contains("<LocationMatch \"^/endpoint/?$\">\n"));
+        assert!(config.contains(
+            "    SetEnvIfNoCase Origin \"^[a-z][a-z0-9+.-]*://[^/?#@]+$\" CORS_ORIGIN=*\n"
+        ));
+        assert!(config.contains(
+            "    SetEnvIfExpr \"%{REQUEST_METHOD} !~ /^(GET|HEAD|OPTIONS)$/\" !CORS_ORIGIN\n"
+        ));
+        assert!(config.contains(
+            "Header always set Access-Control-Allow-Origin \"%{CORS_ORIGIN}e\" env=CORS_ORIGIN\n"
+        ));
+        assert!(config.contains(
+            "Header always set Access-Control-Expose-Headers \"ETag\" env=CORS_ACTUAL_REQUEST\n"
+        ));
+    }
+
+    #[test]
+    fn restricted_origins() {
+        let cors = cors!("/api/items" => Method::Get; "/admin" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://*.example.com"]))
+            .blocked_origins(&["https://evil.example.com"])
+            .allow_credentials(true)
+            .policy_for(
+                "/admin",
+                EndpointPolicy::new()
+                    .allowed_origins(AllowedOrigins::some(&["https://admin.example.com"])),
+            );
+        let config = nginx(&cors).unwrap();
+        assert!(!config.contains('*'));
+        assert!(config.contains(
+            "map $http_origin $cors_origin_0 {\n    default \"\";\n    \
+             \"~(?i)^https://evil\\.example\\.com(:443)?$\" \"\";\n    \
+             \"~(?i)^https://([a-z0-9_-]+\\.)+example\\.com(:443)?$\" $http_origin;\n}\n"
+        ));
+        assert!(config.contains("\"~(?i)^https://admin\\.example\\.com(:443)?$\" $http_origin;\n"));
+        assert!(config.contains("add_header Access-Control-Allow-Credentials \"true\";\n"));
+        assert!(config.contains("add_header Vary \"Origin\";\n"));
+
+        let config = apache(&cors).unwrap();
+        assert!(!config.contains('*'));
+        let allowed = config
+            .find("SetEnvIfNoCase Origin \"^https://([a-z0-9_-]+\\.)+example\\.com(:443)?$\" \
+                   CORS_ORIGIN=$0\n")
+            .unwrap();
+        let blocked = config
+            .find("SetEnvIfNoCase Origin \"^https://evil\\.example\\.com(:443)?$\" !CORS_ORIGIN\n")
+            .unwrap();
+        assert!(allowed < blocked);
+        assert!(config.contains("Header always merge Vary \"Origin\" env=CORS_ORIGIN\n"));
+    }
+
+    #[test]
+    fn refuses_validators() {
+        let cors = cors!("/api/items" => Method::Get)
+            .origin_validator(|origin: &str, _: &Request| origin.ends_with(".example.com"));
+        assert_eq!(nginx(&cors), Err(ProxyError::OriginValidator));
+        assert_eq!(apache(&cors), Err(ProxyError::OriginValidator));
+    }
+}
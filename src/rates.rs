@@ -0,0 +1,304 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Counts of the recent requests of each origin, for origin validators and
+//! response hooks to base their decisions on.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::Request;
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::rates::OriginRates;
+//! use std::time::Duration;
+//!
+//! // Unknown origins get read-only access, up to 100 requests per minute.
+//! let rates = OriginRates::new(Duration::from_secs(60));
+//! let counts = rates.clone();
+//! let cors = cors!("/api/:item" => Method::Get, Method::Post)
+//!     .count_origins(rates)
+//!     .origin_validator(move |origin: &str, request: &Request| {
+//!         origin == "https://app.example.com" ||
+//!             (request.method() == Method::Get && counts.count(origin) <= 100)
+//!     });
+//! # }
+//! ```
+
+use clock::{Clock, SystemClock};
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of buckets windows are divided into: counts are those of the
+/// last window, give or take an eighth of it.
+const BUCKETS: u64 = 8;
+
+/// The most origins counted separately by default.
+pub const DEFAULT_MAX_ORIGINS: usize = 10_000;
+
+/// The requests of one origin, in the buckets up to `last`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Tally {
+    buckets: [usize; BUCKETS as usize],
+    last: u64,
+    /// Whether `DenialMonitor` reported this origin since its count last
+    /// went over the threshold.
+    pub reported: bool,
+}
+
+impl Tally {
+    /// Empties the buckets that are out of the window ending with `now`.
+    fn advance(&mut self, now: u64) {
+        if now <= self.last {
+            return;
+        }
+        for index in 0..cmp::min(now - self.last, BUCKETS) {
+            self.buckets[((now - index) % BUCKETS) as usize] = 0;
+        }
+        self.last = now;
+    }
+
+    /// The number of requests within the window.
+    pub fn count(&self) -> usize {
+        self.buckets.iter().sum()
+    }
+}
+
+/// Requests counted in fixed time buckets, for a bounded number of origins.
+/// Further origins share one count, so that flooding with made up origins
+/// can't grow memory nor escape limits. Origins without recent requests are
+/// forgotten at most once per bucket, rather than on every request.
+#[derive(Debug)]
+pub(crate) struct Counts {
+    bucket: Duration,
+    max_origins: usize,
+    start: Option<Instant>,
+    origins: HashMap<String, Tally>,
+    overflow: Tally,
+    swept: u64,
+}
+
+impl Counts {
+    /// Counts the requests within `window`, for up to `max_origins` origins.
+    pub fn new(window: Duration, max_origins: usize) -> Self {
+        Counts {
+            bucket: cmp::max(window / BUCKETS as u32, Duration::new(0, 1)),
+            max_origins: max_origins,
+            start: None,
+            origins: HashMap::new(),
+            overflow: Tally::default(),
+            swept: 0,
+        }
+    }
+
+    /// Sets the most origins counted separately.
+    pub fn set_max_origins(&mut self, max_origins: usize) {
+        self.max_origins = max_origins;
+    }
+
+    /// The index of the bucket of `now`.
+    fn bucket_of(&mut self, now: Instant) -> u64 {
+        let start = *self.start.get_or_insert(now);
+        if now <= start {
+            return 0;
+        }
+        let nanos = |duration: Duration| {
+            duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+        };
+        nanos(now.duration_since(start)) / nanos(self.bucket)
+    }
+
+    /// Forgets the origins without requests within the window, once per
+    /// bucket.
+    fn sweep(&mut self, now: u64) {
+        if now <= self.swept {
+            return;
+        }
+        self.swept = now;
+        self.origins.retain(|_, tally| now < tally.last + BUCKETS);
+    }
+
+    /// The tally of `origin`, or the shared one if too many origins are
+    /// counted. `None` if it has none.
+    fn tally(&mut self, origin: &str, create: bool) -> Option<&mut Tally> {
+        if !self.origins.contains_key(origin) {
+            if self.origins.len() >= self.max_origins {
+                return Some(&mut self.overflow);
+            }
+            if !create {
+                return None;
+            }
+            self.origins.insert(origin.to_owned(), Tally::default());
+        }
+        self.origins.get_mut(origin)
+    }
+
+    /// Counts a request of `origin` at `now`, returning its tally.
+    pub fn record(&mut self, origin: &str, now: Instant) -> &mut Tally {
+        let now = self.bucket_of(now);
+        self.sweep(now);
+        let tally = self.tally(origin, true).expect("created tally");
+        tally.advance(now);
+        tally.buckets[(now % BUCKETS) as usize] += 1;
+        tally
+    }
+
+    /// The number of requests of `origin` within the window ending at `now`.
+    pub fn count(&mut self, origin: &str, now: Instant) -> usize {
+        let now = self.bucket_of(now);
+        match self.tally(origin, false) {
+            Some(tally) => {
+                tally.advance(now);
+                tally.count()
+            }
+            None => 0,
+        }
+    }
+
+    /// The number of origins counted separately.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.origins.len()
+    }
+}
+
+/// The requests of each origin within a sliding window, counted by the
+/// fairing it is given to with `CORS::count_origins`.
+///
+/// Clones share the same counts, so that one can be kept to query them.
+/// Requests are counted before the origin validator runs, so it sees the
+/// request it is deciding on. Requests without an `Origin` header aren't
+/// counted.
+///
+/// Up to `DEFAULT_MAX_ORIGINS` origins are counted separately, unless set
+/// with `max_origins`; further ones share one count, which is likely to be
+/// higher than theirs.
+#[derive(Clone)]
+pub struct OriginRates {
+    clock: Arc<Clock>,
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl OriginRates {
+    /// Creates counts of the requests within the last `window`.
+    pub fn new(window: Duration) -> Self {
+        OriginRates {
+            clock: Arc::new(SystemClock),
+            counts: Arc::new(Mutex::new(Counts::new(window, DEFAULT_MAX_ORIGINS))),
+        }
+    }
+
+    /// Uses `clock` instead of the system clock. Clones made before keep
+    /// the previous clock.
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets the most origins counted separately, for clones too.
+    pub fn max_origins(self, max_origins: usize) -> Self {
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.set_max_origins(max_origins);
+        }
+        self
+    }
+
+    /// The number of requests of `origin` within the window.
+    pub fn count(&self, origin: &str) -> usize {
+        let now = self.clock.now();
+        self.counts
+            .lock()
+            .map(|mut counts| counts.count(origin, now))
+            .unwrap_or(0)
+    }
+
+    /// Counts a request of `origin`.
+    pub(crate) fn record(&self, origin: &str) {
+        let now = self.clock.now();
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.record(origin, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Counts, OriginRates};
+    use CORS;
+    use clock::MockClock;
+    use rocket::{self, Request};
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+    use std::time::Duration;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn counts_in_window() {
+        let clock = MockClock::new();
+        let rates = OriginRates::new(Duration::from_secs(60)).with_clock(clock.clone());
+        rates.record("https://example.com");
+        clock.advance(Duration::from_secs(30));
+        rates.record("https://example.com");
+        assert_eq!(rates.count("https://example.com"), 2);
+        assert_eq!(rates.count("https://other.com"), 0);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(rates.count("https://example.com"), 1);
+    }
+
+    #[test]
+    fn bounds_origins() {
+        let clock = MockClock::new();
+        let rates = OriginRates::new(Duration::from_secs(60))
+            .with_clock(clock.clone())
+            .max_origins(2);
+        for origin in &["https://a.com", "https://b.com", "https://c.com", "https://d.com"] {
+            rates.record(origin);
+        }
+        assert_eq!(rates.count("https://a.com"), 1);
+        // The other origins share a count.
+        assert_eq!(rates.count("https://c.com"), 2);
+        assert_eq!(rates.count("https://unseen.com"), 2);
+
+        // Origins without recent requests are forgotten.
+        let mut counts = Counts::new(Duration::from_secs(60), 2);
+        let now = clock.now();
+        counts.record("https://a.com", now);
+        counts.record("https://b.com", now + Duration::from_secs(30));
+        counts.record("https://c.com", now + Duration::from_secs(70));
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.count("https://c.com", now + Duration::from_secs(70)), 1);
+    }
+
+    #[test]
+    fn validator_quota() {
+        let rates = OriginRates::new(Duration::from_secs(60));
+        let counts = rates.clone();
+        let cors = cors!("/endpoint" => Method::Get)
+            .count_origins(rates)
+            .origin_validator(move |origin: &str, _: &Request| counts.count(origin) <= 2);
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let allowed = || {
+            client
+                .get("/endpoint")
+                .header(Header::new("Origin", "https://unknown.com"))
+                .dispatch()
+                .headers()
+                .contains("Access-Control-Allow-Origin")
+        };
+        assert!(allowed());
+        assert!(allowed());
+        assert!(!allowed());
+    }
+}
@@ -0,0 +1,155 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! An in-memory log of the decisions taken by the fairing, available with
+//! the `recording` feature.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::recording::DecisionLog;
+//!
+//! let log = DecisionLog::new();
+//! let cors = cors!("/endpoint" => Method::Get).record_into(log.clone());
+//! let rocket = rocket::ignite().attach(cors);
+//! // Dispatch requests, then inspect `log.records()`.
+//! # }
+//! ```
+
+use Decision;
+use rocket::Request;
+use rocket::http::Method;
+use std::sync::{Arc, Mutex};
+
+/// A decision taken for one request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The request method.
+    pub method: Method,
+    /// The request path.
+    pub path: String,
+    /// The value of the `Origin` request header, if any.
+    pub origin: Option<String>,
+    /// The value of the correlation id request header, if one is configured
+    /// with `CORS::correlation_header` and present.
+    pub correlation_id: Option<String>,
+    /// What the fairing decided.
+    pub decision: Decision,
+}
+
+impl Record {
+    pub(crate) fn new(
+        request: &Request,
+        decision: &Decision,
+        correlation_header: Option<&str>,
+    ) -> Self {
+        let headers = request.headers();
+        Record {
+            method: request.method(),
+            path: request.uri().path().to_owned(),
+            origin: headers.get_one("Origin").map(|o| o.to_owned()),
+            correlation_id: correlation_header
+                .and_then(|name| headers.get_one(name))
+                .map(|id| id.to_owned()),
+            decision: decision.clone(),
+        }
+    }
+}
+
+/// A shared log of decisions. Clones of a log all write to the same records.
+#[derive(Clone, Debug, Default)]
+pub struct DecisionLog {
+    records: Arc<Mutex<Vec<Record>>>,
+}
+
+impl DecisionLog {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        DecisionLog::default()
+    }
+
+    /// A copy of the records logged so far, oldest first.
+    pub fn records(&self) -> Vec<Record> {
+        self.records.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// Removes all the records.
+    pub fn clear(&self) {
+        if let Ok(mut records) = self.records.lock() {
+            records.clear();
+        }
+    }
+
+    pub(crate) fn push(&self, record: Record) {
+        if let Ok(mut records) = self.records.lock() {
+            records.push(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DecisionLog;
+    use {Decision, CORS};
+    use rocket;
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn records_decisions() {
+        let log = DecisionLog::new();
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Put; "/endpoint" => Method::Get)
+                .record_into(log.clone()));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        client
+            .get("/endpoint")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        client.delete("/endpoint").dispatch();
+        client.get("/other").dispatch();
+
+        let records = log.records();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].decision, Decision::Allowed(1));
+        assert_eq!(records[0].origin, Some("https://example.com".to_owned()));
+        assert_eq!(records[1].decision, Decision::MethodNotAllowed);
+        assert_eq!(records[1].origin, None);
+        assert_eq!(records[2].decision, Decision::NoMatchingPath);
+        assert_eq!(records[2].path, "/other");
+
+        log.clear();
+        assert!(log.records().is_empty());
+    }
+
+    #[test]
+    fn records_correlation_ids() {
+        let log = DecisionLog::new();
+        let cors = cors!("/endpoint" => Method::Get)
+            .record_into(log.clone())
+            .correlation_header("X-Request-Id");
+        let rocket = rocket::ignite().mount("/", routes![endpoint]).attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        client
+            .delete("/endpoint")
+            .header(Header::new("X-Request-Id", "abc-123"))
+            .dispatch();
+        client.get("/endpoint").dispatch();
+
+        let records = log.records();
+        assert_eq!(records[0].decision, Decision::MethodNotAllowed);
+        assert_eq!(records[0].correlation_id, Some("abc-123".to_owned()));
+        assert_eq!(records[1].correlation_id, None);
+    }
+}
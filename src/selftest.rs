@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Checks of a policy against the decisions critical flows rely on, meant to
+//! run at launch so that a broken configuration fails the deployment.
+//! `CORS::self_test_on_attach` runs them when the fairing is attached, so
+//! that Rocket refuses to launch on a mismatch.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::{Decision, CORS};
+//!
+//! let cors = cors!("/api/:user" => Method::Get);
+//! let report = cors.self_test(&[
+//!     (Method::Get, "/api/alice", Some("https://example.com"), Decision::Allowed(0)),
+//!     (Method::Delete, "/api/alice", None, Decision::MethodNotAllowed),
+//! ]);
+//! if !report.is_ok() {
+//!     panic!("CORS policy self-test failed:\n{}", report);
+//! }
+//! # }
+//! ```
+
+use {Decision, RequestMeta, CORS};
+use rocket::http::Method;
+use std::fmt;
+
+/// A request, as its method, path and `Origin` header, and the decision
+/// expected for it.
+pub type Expectation<'a> = (Method, &'a str, Option<&'a str>, Decision);
+
+/// An expectation the policy doesn't meet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The request method.
+    pub method: Method,
+    /// The request path.
+    pub path: String,
+    /// The value of the `Origin` request header.
+    pub origin: Option<String>,
+    /// The expected decision.
+    pub expected: Decision,
+    /// The decision actually taken.
+    pub actual: Decision,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        if let Some(ref origin) = self.origin {
+            write!(f, " from {}", origin)?;
+        }
+        write!(f, ": expected {:?}, got {:?}", self.expected, self.actual)
+    }
+}
+
+/// The outcome of `CORS::self_test`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The number of expectations checked.
+    pub checked: usize,
+    /// The expectations that weren't met, in the order they were given.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl SelfTestReport {
+    /// Returns true if every expectation was met.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} expectations failed",
+            self.mismatches.len(),
+            self.checked
+        )?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn run(cors: &CORS, expectations: &[Expectation]) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    for &(method, path, origin, ref expected) in expectations {
+        let meta = RequestMeta {
+            origin: origin,
+            ..RequestMeta::new(method, path)
+        };
+        let actual = cors.evaluate_meta(&meta);
+        report.checked += 1;
+        if actual != *expected {
+            report.mismatches.push(Mismatch {
+                method: method,
+                path: path.to_owned(),
+                origin: origin.map(|o| o.to_owned()),
+                expected: expected.clone(),
+                actual: actual,
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use {AllowedOrigins, Decision, CORS};
+    use rocket;
+    use rocket::http::Method;
+    use rocket::local::Client;
+
+    #[test]
+    fn self_test() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://example.com"]));
+        let report = cors.self_test(&[
+            (Method::Get, "/endpoint", Some("https://example.com"), Decision::Allowed(0)),
+            (Method::Get, "/endpoint", Some("https://evil.com"), Decision::Allowed(0)),
+        ]);
+        assert!(!report.is_ok());
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.mismatches[0].actual, Decision::OriginNotAllowed);
+        assert_eq!(
+            report.to_string(),
+            "1 of 2 expectations failed\n  GET /endpoint from https://evil.com: \
+             expected Allowed(0), got OriginNotAllowed"
+        );
+    }
+
+    #[test]
+    fn self_test_on_attach() {
+        let cors = || {
+            cors!("/endpoint" => Method::Get)
+                .allowed_origins(AllowedOrigins::some(&["https://example.com"]))
+        };
+        let passing = cors().self_test_on_attach(&[
+            (Method::Get, "/endpoint", Some("https://example.com"), Decision::Allowed(0)),
+        ]);
+        assert!(Client::new(rocket::ignite().attach(passing)).is_ok());
+
+        let failing = cors().self_test_on_attach(&[
+            (Method::Get, "/endpoint", Some("https://evil.com"), Decision::Allowed(0)),
+        ]);
+        assert!(Client::new(rocket::ignite().attach(failing)).is_err());
+    }
+}
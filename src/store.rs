@@ -0,0 +1,283 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Origin validation backed by a store that can fail, like a database.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::Request;
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::store::{ResilientValidator, StoreFailure};
+//! use std::time::Duration;
+//!
+//! fn lookup(origin: &str, _: &Request) -> Result<bool, String> {
+//!     // Query the tenants table...
+//!     Ok(origin == "https://acme.example.com")
+//! }
+//!
+//! let validator = ResilientValidator::new(lookup)
+//!     .on_failure(StoreFailure::AllowCached)
+//!     .circuit_breaker(5, Duration::from_secs(30))
+//!     .alarm(|message: &str| eprintln!("origin store: {}", message));
+//! let cors = cors!("/endpoint" => Method::Get).origin_validator(validator);
+//! # }
+//! ```
+
+use clock::{Clock, SystemClock};
+use hooks::OriginValidator;
+use rocket::Request;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What to decide when the store can't be consulted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreFailure {
+    /// Deny every origin. This is the default.
+    Deny,
+    /// Allow the origins the store allowed before, and deny the others.
+    AllowCached,
+    /// Allow every origin. Combine with an alarm, since the API is then
+    /// open to all.
+    AllowAll,
+}
+
+/// The state of the circuit breaker.
+#[derive(Debug, Default)]
+struct Breaker {
+    // Failed lookups since the last successful one.
+    failures: usize,
+    // The store isn't consulted until then.
+    open_until: Option<Instant>,
+}
+
+/// An `OriginValidator` consulting a fallible `lookup`, and applying a
+/// fallback decision when it fails.
+///
+/// With a circuit breaker, the store isn't consulted for a while after
+/// several consecutive failures, so that an outage doesn't add latency to
+/// every request. Lookups can't be interrupted: they must enforce their own
+/// timeout, like a statement timeout in the database.
+pub struct ResilientValidator<F> {
+    lookup: F,
+    on_failure: StoreFailure,
+    alarm: Option<Box<Fn(&str) + Send + Sync>>,
+    // Failures opening the breaker and how long it stays open, if enabled.
+    breaker_policy: Option<(usize, Duration)>,
+    breaker: Mutex<Breaker>,
+    clock: Box<Clock>,
+    // The origins the store allowed. The denied ones aren't kept, so that
+    // requests with made up origins can't grow it.
+    allowed: Mutex<HashSet<String>>,
+}
+
+impl<F, E> ResilientValidator<F>
+where
+    F: Fn(&str, &Request) -> Result<bool, E> + Send + Sync + 'static,
+    E: Display,
+{
+    /// Creates a validator consulting `lookup`, which returns whether an
+    /// origin is allowed or why the store couldn't tell.
+    pub fn new(lookup: F) -> Self {
+        ResilientValidator {
+            lookup: lookup,
+            on_failure: StoreFailure::Deny,
+            alarm: None,
+            breaker_policy: None,
+            breaker: Mutex::new(Breaker::default()),
+            clock: Box::new(SystemClock),
+            allowed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Sets the decision taken when the store can't be consulted.
+    pub fn on_failure(mut self, on_failure: StoreFailure) -> Self {
+        self.on_failure = on_failure;
+        self
+    }
+
+    /// Calls `alarm` with a description of each failed lookup, and when the
+    /// circuit breaker opens.
+    pub fn alarm<A>(mut self, alarm: A) -> Self
+    where
+        A: Fn(&str) + Send + Sync + 'static,
+    {
+        self.alarm = Some(Box::new(alarm));
+        self
+    }
+
+    /// Stops consulting the store for `cooldown` after `failures` consecutive
+    /// failed lookups.
+    pub fn circuit_breaker(mut self, failures: usize, cooldown: Duration) -> Self {
+        self.breaker_policy = Some((failures, cooldown));
+        self
+    }
+
+    /// Uses `clock` instead of the system clock for the circuit breaker.
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    fn raise(&self, message: &str) {
+        if let Some(ref alarm) = self.alarm {
+            alarm(message);
+        }
+    }
+
+    fn fallback(&self, origin: &str) -> bool {
+        match self.on_failure {
+            StoreFailure::Deny => false,
+            StoreFailure::AllowCached => self.allowed
+                .lock()
+                .map(|allowed| allowed.contains(origin))
+                .unwrap_or(false),
+            StoreFailure::AllowAll => true,
+        }
+    }
+
+    /// Returns true if the breaker is open, closing it once it has been open
+    /// long enough.
+    fn breaker_open(&self) -> bool {
+        let mut breaker = match self.breaker.lock() {
+            Ok(breaker) => breaker,
+            Err(_) => return false,
+        };
+        let open_until = breaker.open_until;
+        match open_until {
+            Some(until) if self.clock.now() < until => true,
+            Some(_) => {
+                breaker.open_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Counts the result of a lookup, returning true if it opened the breaker.
+    fn count(&self, failed: bool) -> bool {
+        let mut breaker = match self.breaker.lock() {
+            Ok(breaker) => breaker,
+            Err(_) => return false,
+        };
+        if !failed {
+            breaker.failures = 0;
+            return false;
+        }
+        breaker.failures += 1;
+        match self.breaker_policy {
+            Some((failures, cooldown)) if breaker.failures >= failures => {
+                breaker.failures = 0;
+                breaker.open_until = Some(self.clock.now() + cooldown);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<F, E> OriginValidator for ResilientValidator<F>
+where
+    F: Fn(&str, &Request) -> Result<bool, E> + Send + Sync + 'static,
+    E: Display,
+{
+    fn allows(&self, origin: &str, request: &Request) -> bool {
+        if self.breaker_open() {
+            return self.fallback(origin);
+        }
+        match (self.lookup)(origin, request) {
+            Ok(allowed) => {
+                self.count(false);
+                if let Ok(mut cache) = self.allowed.lock() {
+                    if allowed {
+                        cache.insert(origin.to_owned());
+                    } else {
+                        cache.remove(origin);
+                    }
+                }
+                allowed
+            }
+            Err(error) => {
+                self.raise(&format!("lookup of `{}` failed: {}", origin, error));
+                if self.count(true) {
+                    self.raise("circuit breaker opened");
+                }
+                self.fallback(origin)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ResilientValidator, StoreFailure};
+    use CORS;
+    use clock::MockClock;
+    use rocket::{self, Request};
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn degrades_gracefully() {
+        let down = Arc::new(AtomicBool::new(false));
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let alarms = Arc::new(Mutex::new(vec![]));
+        let clock = MockClock::new();
+
+        let (store_down, store_lookups, sink) = (down.clone(), lookups.clone(), alarms.clone());
+        let validator = ResilientValidator::new(move |origin: &str, _: &Request| {
+            store_lookups.fetch_add(1, Ordering::SeqCst);
+            if store_down.load(Ordering::SeqCst) {
+                Err("connection refused")
+            } else {
+                Ok(origin == "https://known.com")
+            }
+        }).on_failure(StoreFailure::AllowCached)
+            .circuit_breaker(2, Duration::from_secs(30))
+            .alarm(move |message: &str| sink.lock().unwrap().push(message.to_owned()))
+            .with_clock(clock.clone());
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get).origin_validator(validator));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let allowed = |origin: &str| {
+            client
+                .get("/endpoint")
+                .header(Header::new("Origin", origin.to_owned()))
+                .dispatch()
+                .headers()
+                .get_one("Access-Control-Allow-Origin")
+                .is_some()
+        };
+
+        assert!(allowed("https://known.com"));
+        down.store(true, Ordering::SeqCst);
+        assert!(allowed("https://known.com"));
+        assert!(!allowed("https://other.com"));
+        assert_eq!(alarms.lock().unwrap().len(), 3);
+        assert_eq!(alarms.lock().unwrap()[2], "circuit breaker opened");
+
+        // The breaker is open: the store isn't consulted anymore.
+        assert!(allowed("https://known.com"));
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+
+        down.store(false, Ordering::SeqCst);
+        clock.advance(Duration::from_secs(30));
+        assert!(allowed("https://known.com"));
+        assert_eq!(lookups.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,120 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Switches turning CORS access to some endpoints off and back on while the
+//! application runs, like in an emergency, without changing the policy.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::policy::EndpointPolicy;
+//! use rocket_cors::switch::Switch;
+//!
+//! let export = Switch::new(true);
+//! let cors = cors!("/api/:item" => Method::Get; "/export" => Method::Get)
+//!     .policy_for("/export", EndpointPolicy::new().enabled_by(export.clone()));
+//!
+//! // Later, from an admin route holding a clone:
+//! export.disable();
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the endpoints it is given to with `EndpointPolicy::enabled_by`
+/// grant CORS access. Disabled endpoints still match requests, which are
+/// denied with `Decision::EndpointDisabled`.
+///
+/// Clones share the same state, so that one can be kept to flip it.
+#[derive(Clone)]
+pub struct Switch(Arc<AtomicBool>);
+
+impl Switch {
+    /// Creates a switch, enabled or not.
+    pub fn new(enabled: bool) -> Self {
+        Switch(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    /// Returns true if the endpoints grant CORS access.
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Enables or disables the endpoints.
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Lets the endpoints grant CORS access again.
+    pub fn enable(&self) {
+        self.set(true);
+    }
+
+    /// Stops the endpoints from granting CORS access.
+    pub fn disable(&self) {
+        self.set(false);
+    }
+}
+
+impl fmt::Debug for Switch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Switch").field(&self.is_enabled()).finish()
+    }
+}
+
+/// Switches are equal if they are clones of one another.
+impl PartialEq for Switch {
+    fn eq(&self, other: &Switch) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Switch {}
+
+#[cfg(test)]
+mod test {
+    use super::Switch;
+    use {Decision, CORS};
+    use policy::EndpointPolicy;
+    use rocket;
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+
+    #[get("/export")]
+    fn export() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn disables_endpoint() {
+        let switch = Switch::new(true);
+        let cors = cors!("/export" => Method::Get)
+            .policy_for("/export", EndpointPolicy::new().enabled_by(switch.clone()));
+        assert_eq!(cors.evaluate(Method::Get, "/export"), Decision::Allowed(0));
+
+        let rocket = rocket::ignite()
+            .mount("/", routes![export])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let allowed = || {
+            client
+                .get("/export")
+                .header(Header::new("Origin", "https://example.com"))
+                .dispatch()
+                .headers()
+                .contains("Access-Control-Allow-Origin")
+        };
+        assert!(allowed());
+        switch.disable();
+        assert!(!allowed());
+        switch.enable();
+        assert!(allowed());
+    }
+}
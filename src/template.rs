@@ -0,0 +1,300 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Parsing of endpoint path templates like "/api/:user/action".
+//!
+//! Variable segments can also use Rocket's route syntax, so "/api/<user>"
+//! is the same template as "/api/:user".
+//!
+//! The parser never panics: any input either produces a `PathTemplate` or a
+//! `TemplateError` describing what is wrong with it.
+
+use rocket::http::uri::URI;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// One segment of a path template.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// A segment that must be equal to the request segment.
+    Literal(String),
+    /// A ':name' or '<name>' segment matching any request segment.
+    Variable(String),
+}
+
+/// A parsed endpoint path template.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+/// The reasons a path template can be rejected.
+/// Positions are byte offsets in the parsed string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The template is an empty string.
+    Empty,
+    /// The template contains an empty segment, like in "/foo//bar" or "/foo/".
+    EmptySegment(usize),
+    /// A variable segment has no name, like in "/foo/:".
+    EmptyVariable(usize),
+    /// The template contains a character that can't appear in a path.
+    InvalidCharacter(usize, char),
+    /// A '<name..>' segment, matching several request segments, which
+    /// templates don't support.
+    MultiSegmentVariable(usize),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::Empty => write!(f, "empty path template"),
+            TemplateError::EmptySegment(pos) => write!(f, "empty segment at position {}", pos),
+            TemplateError::EmptyVariable(pos) => {
+                write!(f, "unnamed variable segment at position {}", pos)
+            }
+            TemplateError::InvalidCharacter(pos, c) => {
+                write!(f, "invalid character {:?} at position {}", c, pos)
+            }
+            TemplateError::MultiSegmentVariable(pos) => {
+                write!(f, "unsupported multiple segments variable at position {}", pos)
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {
+    fn description(&self) -> &str {
+        "invalid path template"
+    }
+}
+
+impl PathTemplate {
+    /// Parses a path template. The leading '/' is optional, and "/" alone is
+    /// the root path.
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        if template.is_empty() {
+            return Err(TemplateError::Empty);
+        }
+        if let Some((pos, c)) = template
+            .char_indices()
+            .find(|&(_, c)| c.is_whitespace() || c.is_control() || c == '?' || c == '#')
+        {
+            return Err(TemplateError::InvalidCharacter(pos, c));
+        }
+
+        let (mut pos, rest) = if template.starts_with('/') {
+            (1, &template[1..])
+        } else {
+            (0, template)
+        };
+        if rest.is_empty() {
+            return Ok(PathTemplate { segments: vec![] });
+        }
+
+        let mut segments = vec![];
+        for segment in rest.split('/') {
+            if segment.is_empty() {
+                return Err(TemplateError::EmptySegment(pos));
+            }
+            if segment.starts_with(':') {
+                if segment.len() == 1 {
+                    return Err(TemplateError::EmptyVariable(pos));
+                }
+                segments.push(Segment::Variable(segment[1..].to_owned()));
+            } else if segment.len() >= 2 && segment.starts_with('<') && segment.ends_with('>') {
+                let name = &segment[1..segment.len() - 1];
+                if name.is_empty() {
+                    return Err(TemplateError::EmptyVariable(pos));
+                }
+                if name.ends_with("..") {
+                    return Err(TemplateError::MultiSegmentVariable(pos));
+                }
+                segments.push(Segment::Variable(name.to_owned()));
+            } else {
+                segments.push(Segment::Literal(segment.to_owned()));
+            }
+            pos += segment.len() + 1;
+        }
+        Ok(PathTemplate { segments: segments })
+    }
+
+    /// The segments of this template.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns true if `path` matches this template.
+    /// Empty segments of `path` are ignored, like Rocket does for request URIs.
+    pub fn matches(&self, path: &str) -> bool {
+        let mut path = path.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            match (segment, path.next()) {
+                (&Segment::Variable(_), Some(_)) => {}
+                (&Segment::Literal(ref expected), Some(actual)) if expected == actual => {}
+                _ => return false,
+            }
+        }
+        path.next().is_none()
+    }
+
+    /// Returns true if every path matching `other` also matches this template.
+    pub fn covers(&self, other: &PathTemplate) -> bool {
+        self.segments.len() == other.segments.len() &&
+            self.segments
+                .iter()
+                .zip(other.segments.iter())
+                .all(|segments| match segments {
+                    (&Segment::Variable(_), _) => true,
+                    (&Segment::Literal(ref a), &Segment::Literal(ref b)) => a == b,
+                    (&Segment::Literal(_), &Segment::Variable(_)) => false,
+                })
+    }
+}
+
+impl FromStr for PathTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PathTemplate::parse(s)
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "/");
+        }
+        for segment in &self.segments {
+            match *segment {
+                Segment::Literal(ref value) => write!(f, "/{}", value)?,
+                Segment::Variable(ref name) => write!(f, "/:{}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Joins `path` under `base`, like a Rocket mount point: "/api" and "/users"
+/// give "/api/users", and "/" is equivalent to no path at all.
+pub(crate) fn join_paths(base: &str, path: &str) -> String {
+    let base = base.trim_right_matches('/');
+    let path = path.trim_left_matches('/');
+    if path.is_empty() && base.is_empty() {
+        "/".to_owned()
+    } else if path.is_empty() {
+        base.to_owned()
+    } else {
+        format!("{}/{}", base, path)
+    }
+}
+
+/// Values usable as an endpoint path: strings holding a template, and
+/// Rocket `URI`s like route paths, whose query part is ignored.
+pub trait IntoEndpointPath {
+    /// Converts this value into an endpoint path template string.
+    fn into_endpoint_path(self) -> String;
+}
+
+impl<'a> IntoEndpointPath for &'a str {
+    fn into_endpoint_path(self) -> String {
+        self.to_owned()
+    }
+}
+
+impl IntoEndpointPath for String {
+    fn into_endpoint_path(self) -> String {
+        self
+    }
+}
+
+impl<'a> IntoEndpointPath for &'a String {
+    fn into_endpoint_path(self) -> String {
+        self.clone()
+    }
+}
+
+impl<'a, 'b> IntoEndpointPath for &'a URI<'b> {
+    fn into_endpoint_path(self) -> String {
+        self.path().to_owned()
+    }
+}
+
+impl<'a> IntoEndpointPath for URI<'a> {
+    fn into_endpoint_path(self) -> String {
+        self.path().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IntoEndpointPath, PathTemplate, Segment, TemplateError};
+    use rocket::http::uri::URI;
+
+    #[test]
+    fn parse_valid() {
+        let template = PathTemplate::parse("/api/:user/action").unwrap();
+        assert_eq!(
+            template.segments(),
+            &[
+                Segment::Literal("api".to_owned()),
+                Segment::Variable("user".to_owned()),
+                Segment::Literal("action".to_owned()),
+            ]
+        );
+        assert_eq!(template.to_string(), "/api/:user/action");
+        assert_eq!(PathTemplate::parse("endpoint").unwrap().to_string(), "/endpoint");
+        assert!(PathTemplate::parse("/").unwrap().segments().is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(PathTemplate::parse(""), Err(TemplateError::Empty));
+        assert_eq!(PathTemplate::parse("/foo//bar"), Err(TemplateError::EmptySegment(5)));
+        assert_eq!(PathTemplate::parse("/foo/"), Err(TemplateError::EmptySegment(5)));
+        assert_eq!(PathTemplate::parse("/foo/:"), Err(TemplateError::EmptyVariable(5)));
+        assert_eq!(
+            PathTemplate::parse("/foo bar"),
+            Err(TemplateError::InvalidCharacter(4, ' '))
+        );
+        assert_eq!(
+            PathTemplate::parse("/é?"),
+            Err(TemplateError::InvalidCharacter(3, '?'))
+        );
+    }
+
+    #[test]
+    fn parse_route_syntax() {
+        let template = PathTemplate::parse("/api/<user>/action").unwrap();
+        assert_eq!(template, PathTemplate::parse("/api/:user/action").unwrap());
+        assert_eq!(PathTemplate::parse("/api/<>"), Err(TemplateError::EmptyVariable(5)));
+        assert_eq!(
+            PathTemplate::parse("/static/<path..>"),
+            Err(TemplateError::MultiSegmentVariable(8))
+        );
+
+        let uri = URI::new("/api/<user>/action?<query>");
+        assert_eq!(uri.into_endpoint_path(), "/api/<user>/action");
+    }
+
+    #[test]
+    fn matches() {
+        let template = PathTemplate::parse("/cors/:something").unwrap();
+        assert!(template.matches("/cors/endpoint"));
+        assert!(!template.matches("/cors"));
+        assert!(!template.matches("/cors/endpoint/more"));
+        assert!(PathTemplate::parse("/").unwrap().matches("/"));
+    }
+
+    #[test]
+    fn covers() {
+        let variable = PathTemplate::parse("/cors/:something").unwrap();
+        let literal = PathTemplate::parse("/cors/endpoint").unwrap();
+        assert!(variable.covers(&literal));
+        assert!(variable.covers(&variable));
+        assert!(!literal.covers(&variable));
+        assert!(!variable.covers(&PathTemplate::parse("/cors").unwrap()));
+    }
+}
@@ -0,0 +1,279 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Canonical cases of a policy, a request and the headers the Fetch standard
+//! and browsers expect on its response, checked by the crate's own tests.
+//!
+//! Applications can check the headers their whole stack sends, including
+//! proxies, against the same cases.
+//!
+//! # Example:
+//! ```
+//! # extern crate rocket_cors;
+//! # fn main() {
+//! use rocket_cors::test_vectors;
+//!
+//! for vector in test_vectors::vectors() {
+//!     let cors = (vector.policy)();
+//!     let headers = cors.headers_for(&vector.request);
+//!     if let Err(failure) = vector.check(headers.as_ref().map(|h| h.as_slice())) {
+//!         panic!("{}: {}", vector.name, failure);
+//!     }
+//! }
+//! # }
+//! ```
+
+use {AllowedHeaders, AllowedOrigins, RequestMeta, CORS};
+use rocket::http::{Header, Method};
+use std::fmt;
+use std::time::Duration;
+
+/// What a response must carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// No CORS access: no `Access-Control-Allow-Origin` header.
+    Denied,
+    /// CORS access, with these headers. A header with a value must be set to
+    /// exactly this value, and one without must be absent.
+    Granted(&'static [(&'static str, Option<&'static str>)]),
+}
+
+/// A case of the expected behavior of a CORS policy.
+pub struct TestVector {
+    /// A short description of the case.
+    pub name: &'static str,
+    /// Builds the policy under test.
+    pub policy: fn() -> CORS,
+    /// The request sent.
+    pub request: RequestMeta<'static>,
+    /// What the response must carry.
+    pub expected: Expected,
+}
+
+/// How the headers of a response differ from those of a test vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VectorFailure {
+    /// CORS access was expected, but not granted.
+    Denied,
+    /// CORS access was granted, but not expected.
+    Granted,
+    /// A header is missing.
+    MissingHeader(&'static str),
+    /// A header has another value than expected.
+    WrongValue(&'static str, String),
+    /// A header that must be absent is set.
+    UnexpectedHeader(&'static str),
+}
+
+impl fmt::Display for VectorFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VectorFailure::Denied => write!(f, "access expected, but denied"),
+            VectorFailure::Granted => write!(f, "access granted, but not expected"),
+            VectorFailure::MissingHeader(name) => write!(f, "missing `{}`", name),
+            VectorFailure::WrongValue(name, ref value) => {
+                write!(f, "unexpected `{}: {}`", name, value)
+            }
+            VectorFailure::UnexpectedHeader(name) => write!(f, "unexpected `{}`", name),
+        }
+    }
+}
+
+impl TestVector {
+    /// Checks the CORS headers of a response, or `None` if it carries none,
+    /// returning the first difference with the expected ones.
+    pub fn check(&self, headers: Option<&[Header]>) -> Result<(), VectorFailure> {
+        let headers = headers.unwrap_or(&[]);
+        let value = |name: &str| {
+            headers
+                .iter()
+                .find(|header| header.name().eq_ignore_ascii_case(name))
+                .map(|header| header.value())
+        };
+        let granted = value("Access-Control-Allow-Origin").is_some();
+        let expected = match self.expected {
+            Expected::Denied if granted => return Err(VectorFailure::Granted),
+            Expected::Denied => return Ok(()),
+            Expected::Granted(_) if !granted => return Err(VectorFailure::Denied),
+            Expected::Granted(expected) => expected,
+        };
+        for &(name, expected) in expected {
+            match (value(name), expected) {
+                (Some(actual), Some(expected)) if actual != expected => {
+                    return Err(VectorFailure::WrongValue(name, actual.to_owned()));
+                }
+                (Some(_), None) => return Err(VectorFailure::UnexpectedHeader(name)),
+                (None, Some(_)) => return Err(VectorFailure::MissingHeader(name)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+const ACAO: &str = "Access-Control-Allow-Origin";
+const ACAC: &str = "Access-Control-Allow-Credentials";
+const ACAM: &str = "Access-Control-Allow-Methods";
+const ACAH: &str = "Access-Control-Allow-Headers";
+const ACMA: &str = "Access-Control-Max-Age";
+const ACEH: &str = "Access-Control-Expose-Headers";
+
+const ORIGIN: &str = "https://app.example.com";
+
+fn actual(origin: &'static str) -> RequestMeta<'static> {
+    RequestMeta {
+        origin: Some(origin),
+        ..RequestMeta::new(Method::Get, "/api/items")
+    }
+}
+
+fn preflight(method: Method) -> RequestMeta<'static> {
+    RequestMeta::preflight("/api/items", ORIGIN, method)
+}
+
+fn open() -> CORS {
+    cors!("/api/items" => Method::Get, Method::Put)
+}
+
+fn listed() -> CORS {
+    open().allowed_origins(AllowedOrigins::some(&[ORIGIN]))
+}
+
+fn credentialed() -> CORS {
+    listed()
+        .allow_credentials(true)
+        .allowed_headers(AllowedHeaders::some(&["X-Custom"]))
+}
+
+/// The canonical cases.
+pub fn vectors() -> Vec<TestVector> {
+    vec![
+        TestVector {
+            name: "any origin gets `*`, which caches can share",
+            policy: open,
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACAO, Some("*")), (ACAC, None), ("Vary", None)]),
+        },
+        TestVector {
+            name: "a listed origin is echoed, varying on it",
+            policy: listed,
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACAO, Some(ORIGIN)), ("Vary", Some("Origin"))]),
+        },
+        TestVector {
+            name: "an unlisted origin is denied",
+            policy: listed,
+            request: actual("https://evil.com"),
+            expected: Expected::Denied,
+        },
+        TestVector {
+            name: "the `null` origin is denied by default",
+            policy: open,
+            request: actual("null"),
+            expected: Expected::Denied,
+        },
+        TestVector {
+            name: "credentials are granted to the exact origin only",
+            policy: credentialed,
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACAO, Some(ORIGIN)), (ACAC, Some("true"))]),
+        },
+        TestVector {
+            name: "a preflight lists the methods of the endpoint",
+            policy: open,
+            request: preflight(Method::Put),
+            expected: Expected::Granted(&[(ACAO, Some("*")), (ACAM, Some("GET, HEAD, PUT"))]),
+        },
+        TestVector {
+            name: "a preflight for another method is denied, when rejecting them",
+            policy: || open().reject_disallowed_methods(true),
+            request: preflight(Method::Delete),
+            expected: Expected::Denied,
+        },
+        TestVector {
+            name: "a credentialed preflight gets the requested headers, as asked",
+            policy: credentialed,
+            request: RequestMeta {
+                request_headers: Some("x-custom"),
+                ..preflight(Method::Put)
+            },
+            expected: Expected::Granted(&[(ACAH, Some("x-custom")), (ACAC, Some("true"))]),
+        },
+        TestVector {
+            name: "the max age is only sent on preflights",
+            policy: || open().max_age(Duration::from_secs(600)),
+            request: preflight(Method::Get),
+            expected: Expected::Granted(&[(ACMA, Some("600"))]),
+        },
+        TestVector {
+            name: "the max age isn't sent on actual requests",
+            policy: || open().max_age(Duration::from_secs(600)),
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACMA, None)]),
+        },
+        TestVector {
+            name: "the max age isn't sent on plain OPTIONS requests",
+            policy: || open().max_age(Duration::from_secs(600)),
+            request: RequestMeta {
+                origin: Some(ORIGIN),
+                ..RequestMeta::new(Method::Options, "/api/items")
+            },
+            expected: Expected::Granted(&[(ACMA, None)]),
+        },
+        TestVector {
+            name: "headers are exposed on actual requests",
+            policy: || open().expose_headers(&["X-Total"]),
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACEH, Some("X-Total"))]),
+        },
+        TestVector {
+            name: "headers aren't exposed on preflights",
+            policy: || open().expose_headers(&["X-Total"]),
+            request: preflight(Method::Get),
+            expected: Expected::Granted(&[(ACEH, None)]),
+        },
+        TestVector {
+            name: "credentialed grants don't expose `*`, which browsers take literally",
+            policy: || credentialed().expose_headers(&["*"]),
+            request: actual(ORIGIN),
+            expected: Expected::Granted(&[(ACAC, Some("true")), (ACEH, None)]),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TestVector, VectorFailure};
+    use rocket::http::Header;
+
+    fn check(vector: &TestVector) -> Result<(), VectorFailure> {
+        let headers = (vector.policy)().headers_for(&vector.request);
+        vector.check(headers.as_ref().map(|h| h.as_slice()))
+    }
+
+    #[test]
+    fn vectors_hold() {
+        for vector in super::vectors() {
+            assert_eq!(check(&vector), Ok(()), "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn reports_differences() {
+        let vectors = super::vectors();
+        let any_origin = &vectors[0];
+        assert_eq!(any_origin.check(None), Err(VectorFailure::Denied));
+        let headers = [
+            Header::new("Access-Control-Allow-Origin", "https://app.example.com"),
+        ];
+        assert_eq!(
+            any_origin.check(Some(&headers)),
+            Err(VectorFailure::WrongValue(
+                "Access-Control-Allow-Origin",
+                "https://app.example.com".to_owned()
+            ))
+        );
+        assert_eq!(vectors[2].check(Some(&headers)), Err(VectorFailure::Granted));
+    }
+}
@@ -0,0 +1,606 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers to write integration tests against CORS enabled Rocket
+//! instances.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket::local::Client;
+//! use rocket_cors::CORS;
+//! use rocket_cors::testing::CorsClient;
+//!
+//! let rocket = rocket::ignite().attach(cors!("/endpoint" => Method::Get));
+//! let client = Client::new(rocket).expect("valid rocket instance");
+//! let (_, grant) = client.preflight("/endpoint", "https://example.com", Method::Get, &[]);
+//! assert_eq!(grant.unwrap().allow_origin, "*");
+//! # }
+//! ```
+
+use {AllowedOrigins, RequestMeta, CORS};
+use hooks::OriginValidator;
+use matching;
+use origins::OriginList;
+use rocket::{Request, Response};
+use rocket::http::{Header, Method};
+use rocket::local::{Client, LocalResponse};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The CORS headers found on a response, parsed into their values.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorsGrant {
+    /// The value of `Access-Control-Allow-Origin`.
+    pub allow_origin: String,
+    /// The methods listed in `Access-Control-Allow-Methods`.
+    pub allow_methods: Vec<String>,
+    /// The headers listed in `Access-Control-Allow-Headers`.
+    pub allow_headers: Vec<String>,
+    /// The headers listed in `Access-Control-Expose-Headers`.
+    pub expose_headers: Vec<String>,
+    /// The value of `Access-Control-Max-Age`, if any.
+    pub max_age: Option<u32>,
+    /// Whether `Access-Control-Allow-Credentials: true` was set.
+    pub allow_credentials: bool,
+}
+
+impl CorsGrant {
+    /// Parses the CORS headers of a response.
+    /// Returns `None` if the response carries no `Access-Control-Allow-Origin`
+    /// header, ie. if no CORS access was granted.
+    pub fn from_response(response: &Response) -> Option<Self> {
+        let headers = response.headers();
+        let allow_origin = match headers.get_one("Access-Control-Allow-Origin") {
+            Some(origin) => origin.to_owned(),
+            None => return None,
+        };
+
+        let list = |name: &str| -> Vec<String> {
+            headers
+                .get(name)
+                .flat_map(|value| value.split(','))
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_owned())
+                .collect()
+        };
+
+        Some(CorsGrant {
+            allow_origin: allow_origin,
+            allow_methods: list("Access-Control-Allow-Methods"),
+            allow_headers: list("Access-Control-Allow-Headers"),
+            expose_headers: list("Access-Control-Expose-Headers"),
+            max_age: headers
+                .get_one("Access-Control-Max-Age")
+                .and_then(|value| value.trim().parse().ok()),
+            allow_credentials: headers.get_one("Access-Control-Allow-Credentials") ==
+                Some("true"),
+        })
+    }
+
+    /// Returns true if the grant lists `method` in its allowed methods.
+    pub fn allows_method(&self, method: Method) -> bool {
+        self.allow_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    /// Returns true if a browser would accept this grant for `origin`.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allow_origin == "*" || self.allow_origin == origin
+    }
+
+    /// Returns true if the grant lists `header` in its allowed headers.
+    pub fn allows_header(&self, header: &str) -> bool {
+        self.allow_headers
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header))
+    }
+}
+
+/// Extension trait for Rocket's local `Client` issuing CORS requests.
+pub trait CorsClient {
+    /// Dispatches a `GET` request to `path` with an `Origin` header.
+    fn cors_get<'c>(&'c self, path: &'c str, origin: &str)
+        -> (LocalResponse<'c>, Option<CorsGrant>);
+
+    /// Dispatches a preflight `OPTIONS` request to `path`, asking permission
+    /// to use `method` and the request `headers` from `origin`.
+    fn preflight<'c>(
+        &'c self,
+        path: &'c str,
+        origin: &str,
+        method: Method,
+        headers: &[&str],
+    ) -> (LocalResponse<'c>, Option<CorsGrant>);
+}
+
+impl CorsClient for Client {
+    fn cors_get<'c>(&'c self, path: &'c str, origin: &str)
+        -> (LocalResponse<'c>, Option<CorsGrant>) {
+        let response = self.get(path)
+            .header(Header::new("Origin", origin.to_owned()))
+            .dispatch();
+        let grant = CorsGrant::from_response(&response);
+        (response, grant)
+    }
+
+    fn preflight<'c>(
+        &'c self,
+        path: &'c str,
+        origin: &str,
+        method: Method,
+        headers: &[&str],
+    ) -> (LocalResponse<'c>, Option<CorsGrant>) {
+        let mut request = self.options(path)
+            .header(Header::new("Origin", origin.to_owned()))
+            .header(Header::new("Access-Control-Request-Method", method.as_str()));
+        if !headers.is_empty() {
+            request = request.header(
+                Header::new("Access-Control-Request-Headers", headers.join(", ")),
+            );
+        }
+        let response = request.dispatch();
+        let grant = CorsGrant::from_response(&response);
+        (response, grant)
+    }
+}
+
+/// The step of a simulated browser flow that would have failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlowFailure {
+    /// The preflight response carried no CORS grant at all.
+    PreflightDenied,
+    /// The preflight granted access to another origin.
+    PreflightOriginMismatch(String),
+    /// The preflight didn't grant the method of the actual request.
+    MethodNotGranted(Method),
+    /// The preflight didn't grant one of the actual request headers.
+    HeaderNotGranted(String),
+    /// The actual response carried no CORS grant.
+    ResponseDenied,
+    /// The actual response granted access to another origin.
+    ResponseOriginMismatch(String),
+}
+
+impl fmt::Display for FlowFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlowFailure::PreflightDenied => write!(f, "preflight: no CORS headers in response"),
+            FlowFailure::PreflightOriginMismatch(ref origin) => {
+                write!(f, "preflight: access granted to origin `{}`", origin)
+            }
+            FlowFailure::MethodNotGranted(method) => {
+                write!(f, "preflight: method `{}` not allowed", method)
+            }
+            FlowFailure::HeaderNotGranted(ref header) => {
+                write!(f, "preflight: header `{}` not allowed", header)
+            }
+            FlowFailure::ResponseDenied => write!(f, "request: no CORS headers in response"),
+            FlowFailure::ResponseOriginMismatch(ref origin) => {
+                write!(f, "request: access granted to origin `{}`", origin)
+            }
+        }
+    }
+}
+
+/// Runs the sequence a browser would go through for a cross-origin request:
+/// a preflight asking for `method` and the names of `headers`, a check of the
+/// granted permissions, then the actual request with those headers.
+/// Returns the actual response if every step succeeded, or the first step
+/// that would have made the browser block the request.
+pub fn browser_flow<'c>(
+    client: &'c Client,
+    method: Method,
+    path: &'c str,
+    origin: &str,
+    headers: &[(&str, &str)],
+) -> Result<LocalResponse<'c>, FlowFailure> {
+    let names: Vec<&str> = headers.iter().map(|&(name, _)| name).collect();
+    let (_, grant) = client.preflight(path, origin, method, &names);
+    let grant = grant.ok_or(FlowFailure::PreflightDenied)?;
+    if !grant.allows_origin(origin) {
+        return Err(FlowFailure::PreflightOriginMismatch(grant.allow_origin));
+    }
+    if !grant.allows_method(method) {
+        return Err(FlowFailure::MethodNotGranted(method));
+    }
+    if let Some(name) = names.iter().find(|name| !grant.allows_header(name)) {
+        return Err(FlowFailure::HeaderNotGranted(name.to_string()));
+    }
+
+    let mut request = client
+        .req(method, path)
+        .header(Header::new("Origin", origin.to_owned()));
+    for &(name, value) in headers {
+        request = request.header(Header::new(name.to_owned(), value.to_owned()));
+    }
+    let response = request.dispatch();
+    match CorsGrant::from_response(&response) {
+        None => Err(FlowFailure::ResponseDenied),
+        Some(ref grant) if !grant.allows_origin(origin) => {
+            Err(FlowFailure::ResponseOriginMismatch(grant.allow_origin.clone()))
+        }
+        Some(_) => Ok(response),
+    }
+}
+
+/// An `OriginValidator` answering with a closure and recording the origins
+/// it was asked about. Clones share the recorded calls, so keep one to
+/// inspect them once the validator is given to the fairing.
+pub struct FnOriginValidator<F> {
+    answer: Arc<F>,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl<F> FnOriginValidator<F>
+where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+    /// Creates a validator allowing the origins for which `answer` returns
+    /// true.
+    pub fn new(answer: F) -> Self {
+        FnOriginValidator {
+            answer: Arc::new(answer),
+            calls: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// The origins the validator was asked about, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().map(|calls| calls.clone()).unwrap_or_default()
+    }
+}
+
+impl<F> Clone for FnOriginValidator<F> {
+    fn clone(&self) -> Self {
+        FnOriginValidator {
+            answer: self.answer.clone(),
+            calls: self.calls.clone(),
+        }
+    }
+}
+
+impl<F> OriginValidator for FnOriginValidator<F>
+where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+    fn allows(&self, origin: &str, _: &Request) -> bool {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(origin.to_owned());
+        }
+        (self.answer)(origin)
+    }
+}
+
+#[derive(Debug, Default)]
+struct StoreState {
+    answers: HashMap<String, bool>,
+    failure: Option<String>,
+    calls: Vec<String>,
+}
+
+/// An origin store with scripted answers, standing in for a database behind
+/// a `store::ResilientValidator`. Unknown origins are denied. Clones share
+/// the answers and the recorded lookups.
+///
+/// ```
+/// # #[macro_use] extern crate rocket_cors;
+/// # extern crate rocket;
+/// # fn main() {
+/// use rocket::Request;
+/// use rocket::http::Method;
+/// use rocket_cors::CORS;
+/// use rocket_cors::store::ResilientValidator;
+/// use rocket_cors::testing::MockOriginStore;
+///
+/// let store = MockOriginStore::new().answer("https://acme.example.com", true);
+/// let lookup = store.clone();
+/// let validator = ResilientValidator::new(move |origin: &str, _: &Request| {
+///     lookup.lookup(origin)
+/// });
+/// let cors = cors!("/endpoint" => Method::Get).origin_validator(validator);
+/// store.fail("connection refused");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockOriginStore {
+    state: Arc<Mutex<StoreState>>,
+}
+
+impl MockOriginStore {
+    /// Creates a store denying every origin.
+    pub fn new() -> Self {
+        MockOriginStore::default()
+    }
+
+    /// Answers lookups of `origin` with `allowed`.
+    pub fn answer(self, origin: &str, allowed: bool) -> Self {
+        if let Ok(mut state) = self.state.lock() {
+            state.answers.insert(origin.to_owned(), allowed);
+        }
+        self
+    }
+
+    /// Fails every lookup with `message` until `recover` is called, like a
+    /// store that went down.
+    pub fn fail(&self, message: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.failure = Some(message.to_owned());
+        }
+    }
+
+    /// Answers lookups again after `fail`.
+    pub fn recover(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.failure = None;
+        }
+    }
+
+    /// Looks `origin` up, recording the call.
+    pub fn lookup(&self, origin: &str) -> Result<bool, String> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Err("poisoned store".to_owned()),
+        };
+        state.calls.push(origin.to_owned());
+        match state.failure {
+            Some(ref message) => Err(message.clone()),
+            None => Ok(state.answers.get(origin).cloned().unwrap_or(false)),
+        }
+    }
+
+    /// The origins looked up, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.state.lock().map(|state| state.calls.clone()).unwrap_or_default()
+    }
+}
+
+/// A representative request generated from a policy, along with whether the
+/// policy is expected to grant CORS access to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixture {
+    /// The request method.
+    pub method: Method,
+    /// The request path.
+    pub path: String,
+    /// The value of the `Origin` request header.
+    pub origin: String,
+    /// True if the response must carry CORS headers.
+    pub expect_cors: bool,
+}
+
+/// The methods used when generating fixtures.
+const FIXTURE_METHODS: [Method; 6] = [
+    Method::Get,
+    Method::Put,
+    Method::Post,
+    Method::Delete,
+    Method::Patch,
+    Method::Options,
+];
+
+/// Pushes an origin matching each of `entries` to `origins`, turning
+/// patterns like "https://*.myapp.io" into origins like
+/// "https://example.myapp.io".
+fn push_examples(origins: &mut Vec<String>, entries: &OriginList) {
+    for entry in entries.parsed() {
+        let example = entry.example();
+        if !origins.contains(&example) {
+            origins.push(example);
+        }
+    }
+}
+
+/// The origins used when generating fixtures: one matching each allowed or
+/// blocked entry of `cors` and of its endpoints, `null` and an unlisted one.
+fn fixture_origins(cors: &CORS) -> Vec<String> {
+    let mut origins = vec!["https://example.com".to_owned()];
+    let endpoint_origins = cors.endpoint_policies
+        .iter()
+        .filter_map(|policy| policy.allowed_origins.as_ref());
+    for allowed in Some(&cors.allowed_origins).into_iter().chain(endpoint_origins) {
+        if let AllowedOrigins::Some(ref entries) = *allowed {
+            push_examples(&mut origins, entries);
+        }
+    }
+    push_examples(&mut origins, &cors.blocked_origins);
+    origins.push("null".to_owned());
+    origins.push("https://unlisted.invalid".to_owned());
+    origins
+}
+
+/// Enumerates requests covering every endpoint of `cors`: each method, on
+/// a path matching the endpoint and on one that doesn't, from each origin.
+/// Whether they get CORS access is decided by `CORS::evaluate_meta`.
+/// Requests it allows are left out if `cors` has an origin validator,
+/// since their outcome depends on it.
+pub fn fixtures(cors: &CORS) -> Vec<Fixture> {
+    let origins = fixture_origins(cors);
+    let mut fixtures = vec![];
+    for &(_, ref template) in cors.endpoints() {
+        let matched = matching::literalize(template);
+        let unmatched = format!("{}/unmatched", matched);
+        for path in &[matched, unmatched] {
+            for &method in &FIXTURE_METHODS {
+                for origin in &origins {
+                    let meta = RequestMeta {
+                        origin: Some(origin.as_str()),
+                        ..RequestMeta::new(method, path)
+                    };
+                    let expect_cors = cors.evaluate_meta(&meta).is_allowed();
+                    if expect_cors && cors.origin_validator.is_some() {
+                        continue;
+                    }
+                    fixtures.push(Fixture {
+                        method: method,
+                        path: path.clone(),
+                        origin: origin.clone(),
+                        expect_cors: expect_cors,
+                    });
+                }
+            }
+        }
+    }
+    fixtures
+}
+
+#[cfg(test)]
+mod test {
+    use super::{browser_flow, fixtures, CorsClient, Fixture, FlowFailure};
+    use super::{FnOriginValidator, MockOriginStore};
+    use {AllowedOrigins, CORS};
+    use rocket::{self, Request};
+    use store::{ResilientValidator, StoreFailure};
+    use rocket::http::{Header, Method};
+    use rocket::local::Client;
+
+    #[get("/endpoint")]
+    fn endpoint() -> &'static str {
+        "Hello World!"
+    }
+
+    #[test]
+    fn cors_get_parses_grant() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let (_, grant) = client.cors_get("/endpoint", "https://example.com");
+        let grant = grant.expect("a CORS grant");
+        assert_eq!(grant.allow_origin, "*");
+        assert!(grant.allows_method(Method::Get));
+        assert!(grant.allows_header("Content-Type"));
+        assert!(!grant.allow_credentials);
+    }
+
+    #[test]
+    fn preflight_without_cors() {
+        let rocket = rocket::ignite().mount("/", routes![endpoint]);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let (_, grant) = client.preflight("/endpoint", "https://example.com", Method::Get, &[]);
+        assert_eq!(grant, None);
+    }
+
+    #[test]
+    fn browser_flow_succeeds() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let mut response = browser_flow(
+            &client,
+            Method::Get,
+            "/endpoint",
+            "https://example.com",
+            &[("Accept", "text/plain")],
+        ).expect("successful flow");
+        let body_str = response.body().and_then(|b| b.into_string());
+        assert_eq!(body_str, Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn browser_flow_reports_header() {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors!("/endpoint" => Method::Get));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let result = browser_flow(
+            &client,
+            Method::Get,
+            "/endpoint",
+            "https://example.com",
+            &[("X-Custom", "1")],
+        );
+        assert_eq!(
+            result.err(),
+            Some(FlowFailure::HeaderNotGranted("X-Custom".to_string()))
+        );
+    }
+
+    fn check_fixtures(cors: CORS, fixtures: Vec<Fixture>) {
+        let rocket = rocket::ignite()
+            .mount("/", routes![endpoint])
+            .attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        for fixture in fixtures {
+            let response = client
+                .req(fixture.method, fixture.path.clone())
+                .header(Header::new("Origin", fixture.origin.clone()))
+                .dispatch();
+            let has_cors = response.headers().contains("Access-Control-Allow-Origin");
+            assert_eq!(has_cors, fixture.expect_cors, "{:?}", fixture);
+        }
+    }
+
+    #[test]
+    fn fixtures_match_responses() {
+        let cors = cors!("/endpoint" => Method::Get; "/api/:user" => Method::Put, Method::Delete);
+        let fixtures = fixtures(&cors);
+        // "https://example.com", "null" and "https://unlisted.invalid".
+        assert_eq!(fixtures.len(), 2 * 2 * 6 * 3);
+        check_fixtures(cors, fixtures);
+    }
+
+    #[test]
+    fn fixtures_follow_patterns_and_blocks() {
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://*.myapp.io"]))
+            .blocked_origins(&["https://evil.myapp.io"]);
+        let fixtures = fixtures(&cors);
+        let expects = |origin: &str| {
+            fixtures
+                .iter()
+                .find(|f| f.origin == origin && f.method == Method::Get && f.path == "/endpoint")
+                .map(|f| f.expect_cors)
+        };
+        assert_eq!(expects("https://example.myapp.io"), Some(true));
+        assert_eq!(expects("https://evil.myapp.io"), Some(false));
+        assert_eq!(expects("null"), Some(false));
+        assert_eq!(expects("https://*.myapp.io"), None);
+        check_fixtures(cors, fixtures);
+    }
+
+    #[test]
+    fn fn_origin_validator() {
+        let validator = FnOriginValidator::new(|origin: &str| origin.ends_with(".example.com"));
+        let cors = cors!("/endpoint" => Method::Get).origin_validator(validator.clone());
+        let rocket = rocket::ignite().mount("/", routes![endpoint]).attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        assert!(client.cors_get("/endpoint", "https://app.example.com").1.is_some());
+        assert!(client.cors_get("/endpoint", "https://evil.com").1.is_none());
+        assert_eq!(
+            validator.calls(),
+            vec!["https://app.example.com".to_owned(), "https://evil.com".to_owned()]
+        );
+    }
+
+    #[test]
+    fn mock_origin_store() {
+        let store = MockOriginStore::new().answer("https://acme.example.com", true);
+        let lookup = store.clone();
+        let validator = ResilientValidator::new(move |origin: &str, _: &Request| {
+            lookup.lookup(origin)
+        }).on_failure(StoreFailure::AllowCached);
+        let cors = cors!("/endpoint" => Method::Get).origin_validator(validator);
+        let rocket = rocket::ignite().mount("/", routes![endpoint]).attach(cors);
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let allowed = |origin: &str| client.cors_get("/endpoint", origin).1.is_some();
+
+        assert!(allowed("https://acme.example.com"));
+        assert!(!allowed("https://other.example.com"));
+        // Origins allowed before still are while the store is down.
+        store.fail("connection refused");
+        assert!(allowed("https://acme.example.com"));
+        assert!(!allowed("https://other.example.com"));
+        store.recover();
+        assert!(!allowed("https://other.example.com"));
+        assert_eq!(store.calls().len(), 5);
+    }
+}
@@ -0,0 +1,148 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A histogram of the time the fairing spends on each response, to export
+//! to a metrics system.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::CORS;
+//! use rocket_cors::timing::Timings;
+//!
+//! let timings = Timings::new();
+//! let cors = cors!("/endpoint" => Method::Get).time_into(timings.clone());
+//! // Periodically export `timings.snapshot()`.
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The upper bounds of the histogram buckets, in microseconds.
+const BUCKETS: [u64; 8] = [50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// The timings recorded so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimingSnapshot {
+    /// The number of timed responses.
+    pub count: u64,
+    /// The total time spent.
+    pub total: Duration,
+    /// The number of responses that took at most each bound, cumulatively
+    /// like Prometheus buckets. The last bucket, without bound, counts them
+    /// all.
+    pub buckets: Vec<(Option<Duration>, u64)>,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    count: u64,
+    total: Duration,
+    // Counts per bucket, including the unbounded one, not cumulative.
+    counts: [u64; 9],
+}
+
+/// A shared histogram. Clones of it all record into the same histogram.
+#[derive(Clone, Debug)]
+pub struct Timings {
+    histogram: Arc<Mutex<Histogram>>,
+}
+
+impl Timings {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Timings {
+            histogram: Arc::new(Mutex::new(Histogram {
+                count: 0,
+                total: Duration::from_secs(0),
+                counts: [0; 9],
+            })),
+        }
+    }
+
+    /// The timings recorded so far.
+    pub fn snapshot(&self) -> TimingSnapshot {
+        let histogram = match self.histogram.lock() {
+            Ok(histogram) => histogram,
+            Err(_) => return TimingSnapshot::default(),
+        };
+        let mut cumulative = 0;
+        let buckets = histogram
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| {
+                cumulative += *count;
+                let bound = BUCKETS
+                    .get(index)
+                    .map(|micros| Duration::new(0, *micros as u32 * 1_000));
+                (bound, cumulative)
+            })
+            .collect();
+        TimingSnapshot {
+            count: histogram.count,
+            total: histogram.total,
+            buckets: buckets,
+        }
+    }
+
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_nanos() / 1_000);
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKETS.len());
+        if let Ok(mut histogram) = self.histogram.lock() {
+            histogram.count += 1;
+            histogram.total += elapsed;
+            histogram.counts[bucket] += 1;
+        }
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Timings::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Timings;
+    use CORS;
+    use rocket;
+    use rocket::http::Method;
+    use rocket::local::Client;
+    use std::time::Duration;
+
+    #[test]
+    fn histogram() {
+        let timings = Timings::new();
+        timings.record(Duration::new(0, 30_000));
+        timings.record(Duration::new(0, 700_000));
+        timings.record(Duration::from_secs(1));
+
+        let snapshot = timings.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.total, Duration::new(1, 730_000));
+        assert_eq!(snapshot.buckets[0], (Some(Duration::new(0, 50_000)), 1));
+        assert_eq!(snapshot.buckets[4], (Some(Duration::from_millis(1)), 2));
+        assert_eq!(snapshot.buckets[8], (None, 3));
+    }
+
+    #[test]
+    fn times_responses() {
+        let timings = Timings::new();
+        let rocket = rocket::ignite()
+            .attach(cors!("/endpoint" => Method::Get).time_into(timings.clone()));
+        let client = Client::new(rocket).expect("valid rocket instance");
+        client.get("/endpoint").dispatch();
+        client.options("/endpoint").dispatch();
+        assert_eq!(timings.snapshot().count, 2);
+    }
+}
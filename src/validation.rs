@@ -0,0 +1,383 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Detection of configurations that browsers refuse, or that grant more
+//! than intended, before the fairing is attached.
+//!
+//! `CORS::validate` refuses the configurations with errors, while
+//! `CORS::validation_report` also lists warnings about those that work but
+//! are likely mistakes, so that CI can fail on them too.
+//!
+//! # Example:
+//! ```
+//! # #[macro_use] extern crate rocket_cors;
+//! # extern crate rocket;
+//! # fn main() {
+//! use rocket::http::Method;
+//! use rocket_cors::{AllowedOrigins, CORS};
+//!
+//! let cors = cors!("/endpoint" => Method::Get)
+//!     .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]))
+//!     .allow_credentials(true)
+//!     .validate()
+//!     .expect("valid CORS configuration");
+//! # }
+//! ```
+
+use {AllowedHeaders, AllowedOrigins, CORS};
+use lint::{self, Lint};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use template::{PathTemplate, TemplateError};
+
+/// The longest `Access-Control-Max-Age` Chromium honors, in seconds.
+const MAX_AGE_CAP: u64 = 7200;
+
+/// A configuration mistake found by `CORS::validate`.
+/// Endpoints are identified by their index in `CORS::endpoints()`, and
+/// settings of the whole fairing by `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The path of the endpoint isn't a valid template.
+    InvalidPath(usize, TemplateError),
+    /// The endpoint doesn't allow any method.
+    NoMethods(usize),
+    /// `CORS::allowed_methods` advertises no method.
+    NoAdvertisedMethods,
+    /// Credentials are allowed to any origin, so that any site can act on
+    /// behalf of the users of the API.
+    CredentialsWithAnyOrigin(Option<usize>),
+    /// The allowed headers list `*` while credentials are allowed, for which
+    /// browsers take it literally.
+    WildcardHeaderWithCredentials(Option<usize>),
+    /// A policy is given with `CORS::policy_for` to this path, which no
+    /// endpoint is declared with, so that it never applies.
+    UnmatchedPolicy(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scope = |endpoint: Option<usize>| match endpoint {
+            Some(index) => format!("endpoint {}", index),
+            None => "the fairing".to_owned(),
+        };
+        match *self {
+            ConfigError::InvalidPath(index, ref error) => {
+                write!(f, "endpoint {} has an invalid path: {}", index, error)
+            }
+            ConfigError::NoMethods(index) => write!(f, "endpoint {} allows no method", index),
+            ConfigError::NoAdvertisedMethods => write!(f, "no method is advertised"),
+            ConfigError::CredentialsWithAnyOrigin(endpoint) => {
+                write!(f, "{} allows credentials to any origin", scope(endpoint))
+            }
+            ConfigError::WildcardHeaderWithCredentials(endpoint) => write!(
+                f,
+                "{} allows credentials with a `*` allowed header",
+                scope(endpoint)
+            ),
+            ConfigError::UnmatchedPolicy(ref path) => {
+                write!(f, "no endpoint has the path `{}` of a policy", path)
+            }
+        }
+    }
+}
+
+impl ConfigError {
+    /// A stable identifier of the kind of error, like "no-methods".
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ConfigError::InvalidPath(..) => "invalid-path",
+            ConfigError::NoMethods(_) => "no-methods",
+            ConfigError::NoAdvertisedMethods => "no-advertised-methods",
+            ConfigError::CredentialsWithAnyOrigin(_) => "credentials-with-any-origin",
+            ConfigError::WildcardHeaderWithCredentials(_) => "wildcard-header-with-credentials",
+            ConfigError::UnmatchedPolicy(_) => "unmatched-policy",
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        "invalid CORS configuration"
+    }
+}
+
+/// A configuration that works, but is likely a mistake.
+/// Endpoints are identified as in `ConfigError`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// The endpoint never matches, because of earlier ones. See `Lint`.
+    ShadowedEndpoint(usize),
+    /// The `null` origin is allowed, which any site can send from a sandboxed
+    /// iframe.
+    NullOriginAllowed,
+    /// The max age is longer than browsers honor, so they cut it short.
+    MaxAgeCapped(Option<usize>),
+    /// Any origin is reflected, with neither a list of origins nor a
+    /// validator: this is `*` without its restrictions.
+    ReflectsAnyOrigin,
+    /// This entry given to `CORS::temporary_origin` has expired, and can be
+    /// removed.
+    ExpiredOrigin(String),
+}
+
+impl Warning {
+    /// A stable identifier of the kind of warning, like "null-origin-allowed".
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Warning::ShadowedEndpoint(_) => "shadowed-endpoint",
+            Warning::NullOriginAllowed => "null-origin-allowed",
+            Warning::MaxAgeCapped(_) => "max-age-capped",
+            Warning::ReflectsAnyOrigin => "reflects-any-origin",
+            Warning::ExpiredOrigin(_) => "expired-origin",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::ShadowedEndpoint(index) => {
+                write!(f, "endpoint {} is shadowed by earlier endpoints", index)
+            }
+            Warning::NullOriginAllowed => write!(f, "the `null` origin is allowed"),
+            Warning::MaxAgeCapped(Some(index)) => write!(
+                f,
+                "endpoint {} has a max age over {} seconds, which browsers cap",
+                index, MAX_AGE_CAP
+            ),
+            Warning::MaxAgeCapped(None) => write!(
+                f,
+                "the max age is over {} seconds, which browsers cap",
+                MAX_AGE_CAP
+            ),
+            Warning::ReflectsAnyOrigin => write!(f, "any origin is reflected"),
+            Warning::ExpiredOrigin(ref entry) => {
+                write!(f, "the temporary origin `{}` has expired", entry)
+            }
+        }
+    }
+}
+
+/// The errors and warnings found in a configuration by
+/// `CORS::validation_report`. Its display has a line per finding, like
+/// `warning[max-age-capped]: the max age is over 7200 seconds, which browsers cap`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The mistakes `CORS::validate` refuses.
+    pub errors: Vec<ConfigError>,
+    /// The likely mistakes.
+    pub warnings: Vec<Warning>,
+}
+
+impl ValidationReport {
+    /// Returns true if there is no error.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns true if there is neither an error nor a warning.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error[{}]: {}", error.code(), error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning[{}]: {}", warning.code(), warning)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn errors(cors: &CORS) -> Vec<ConfigError> {
+    let mut errors = vec![];
+    for (index, &(ref methods, ref path)) in cors.allowed_endpoints.iter().enumerate() {
+        if let Err(error) = PathTemplate::parse(path) {
+            errors.push(ConfigError::InvalidPath(index, error));
+        }
+        if methods.is_empty() {
+            errors.push(ConfigError::NoMethods(index));
+        }
+    }
+    if cors.advertised_methods
+        .as_ref()
+        .map_or(false, |methods| methods.as_slice().is_empty())
+    {
+        errors.push(ConfigError::NoAdvertisedMethods);
+    }
+    for path in &cors.unmatched_policies {
+        errors.push(ConfigError::UnmatchedPolicy(path.clone()));
+    }
+
+    // An origin validator restricts the origins, even when all are listed.
+    let any_origin = |origins: &AllowedOrigins| {
+        *origins == AllowedOrigins::All && cors.origin_validator.is_none()
+    };
+    let wildcard_header = |headers: &AllowedHeaders| match *headers {
+        AllowedHeaders::Some(ref headers) => headers.iter().any(|h| h == "*"),
+        AllowedHeaders::All | AllowedHeaders::Echo => false,
+    };
+    if cors.allow_credentials {
+        if any_origin(&cors.allowed_origins) {
+            errors.push(ConfigError::CredentialsWithAnyOrigin(None));
+        }
+        if wildcard_header(&cors.allowed_headers) {
+            errors.push(ConfigError::WildcardHeaderWithCredentials(None));
+        }
+    }
+    // Endpoints are only reported for the settings they override.
+    for (index, policy) in cors.endpoint_policies.iter().enumerate() {
+        let credentials = policy.allow_credentials.unwrap_or(cors.allow_credentials);
+        if !credentials {
+            continue;
+        }
+        let overrides_credentials = policy.allow_credentials.is_some() && !cors.allow_credentials;
+        let origins = policy.allowed_origins.as_ref();
+        if (overrides_credentials || origins.is_some()) &&
+            any_origin(origins.unwrap_or(&cors.allowed_origins))
+        {
+            errors.push(ConfigError::CredentialsWithAnyOrigin(Some(index)));
+        }
+        let headers = policy.allowed_headers.as_ref();
+        if (overrides_credentials || headers.is_some()) &&
+            wildcard_header(headers.unwrap_or(&cors.allowed_headers))
+        {
+            errors.push(ConfigError::WildcardHeaderWithCredentials(Some(index)));
+        }
+    }
+    errors
+}
+
+pub(crate) fn report(cors: &CORS) -> ValidationReport {
+    let mut warnings: Vec<Warning> = lint::lint(cors)
+        .into_iter()
+        .filter_map(|lint| match lint {
+            Lint::ShadowedEndpoint(index) => Some(Warning::ShadowedEndpoint(index)),
+            // Already an error.
+            Lint::InvalidPath(..) => None,
+        })
+        .collect();
+    if cors.allow_null_origin {
+        warnings.push(Warning::NullOriginAllowed);
+    }
+    let capped = |max_age: Option<Duration>| {
+        max_age.map_or(false, |max_age| max_age.as_secs() > MAX_AGE_CAP)
+    };
+    if capped(cors.max_age) {
+        warnings.push(Warning::MaxAgeCapped(None));
+    }
+    for (index, policy) in cors.endpoint_policies.iter().enumerate() {
+        if capped(policy.max_age) {
+            warnings.push(Warning::MaxAgeCapped(Some(index)));
+        }
+    }
+    if cors.reflect_origin && cors.allowed_origins == AllowedOrigins::All &&
+        cors.origin_validator.is_none()
+    {
+        warnings.push(Warning::ReflectsAnyOrigin);
+    }
+    for entry in cors.expired_origins() {
+        warnings.push(Warning::ExpiredOrigin(entry.to_owned()));
+    }
+    ValidationReport {
+        errors: errors(cors),
+        warnings: warnings,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigError, Warning};
+    use {AllowedHeaders, AllowedOrigins, Decision, MethodSet, RequestMeta, CORS};
+    use policy::EndpointPolicy;
+    use rocket::http::Method;
+    use std::time::Duration;
+
+    #[test]
+    fn reports_errors() {
+        let cors = CORS::new(vec![(vec![], "/endpoint".to_owned())])
+            .allowed_methods(MethodSet::empty())
+            .allow_credentials(true)
+            .allowed_headers(AllowedHeaders::some(&["*"]));
+        assert_eq!(
+            super::errors(&cors),
+            vec![
+                ConfigError::NoMethods(0),
+                ConfigError::NoAdvertisedMethods,
+                ConfigError::CredentialsWithAnyOrigin(None),
+                ConfigError::WildcardHeaderWithCredentials(None),
+            ]
+        );
+
+        let cors = cors!("/public" => Method::Get; "/admin" => Method::Get).policy_for(
+            "/admin",
+            EndpointPolicy::new().allow_credentials(true),
+        );
+        assert_eq!(
+            cors.validate().err(),
+            Some(ConfigError::CredentialsWithAnyOrigin(Some(1)))
+        );
+
+        let cors = cors!("/endpoint" => Method::Get)
+            .allowed_origins(AllowedOrigins::some(&["https://app.example.com"]))
+            .allow_credentials(true);
+        assert!(cors.validate().is_ok());
+    }
+
+    #[test]
+    fn reports_unmatched_policies() {
+        let admin = || {
+            EndpointPolicy::new().allowed_origins(AllowedOrigins::some(&["https://admin.com"]))
+        };
+        let cors = cors!("/admin/:page" => Method::Get)
+            .policy_for("/admin/<page>", admin())
+            .policy_for("/admin/:page/", admin())
+            .policy_for("/admn/:page", admin());
+        assert_eq!(
+            super::errors(&cors),
+            vec![
+                ConfigError::UnmatchedPolicy("/admin/:page/".to_owned()),
+                ConfigError::UnmatchedPolicy("/admn/:page".to_owned()),
+            ]
+        );
+        // The policy for "/admin/<page>" applies.
+        let meta = RequestMeta {
+            origin: Some("https://example.com"),
+            ..RequestMeta::new(Method::Get, "/admin/home")
+        };
+        assert_eq!(cors.evaluate_meta(&meta), Decision::OriginNotAllowed);
+    }
+
+    #[test]
+    fn reports_warnings() {
+        let cors = cors!("/api/:user" => Method::Get; "/api/admin" => Method::Get)
+            .allow_null_origin(true)
+            .max_age(Duration::from_secs(86_400))
+            .allow_credentials(true);
+        let report = cors.validation_report();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::ShadowedEndpoint(1),
+                Warning::NullOriginAllowed,
+                Warning::MaxAgeCapped(None),
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "error[credentials-with-any-origin]: the fairing allows credentials to any origin\n\
+             warning[shadowed-endpoint]: endpoint 1 is shadowed by earlier endpoints\n\
+             warning[null-origin-allowed]: the `null` origin is allowed\n\
+             warning[max-age-capped]: the max age is over 7200 seconds, which browsers cap\n"
+        );
+        assert!(cors!("/endpoint" => Method::Get).validation_report().is_clean());
+    }
+}